@@ -0,0 +1,107 @@
+use std::{env, fs};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use crate::cli::Args;
+use crate::source::{ParseError, Source};
+
+/// default directory of the build cache, relative to the working directory
+pub(crate) const CACHE_DIR: &str = ".mi-cache";
+
+/// 64 bit FNV-1a, stable across runs and compiler versions unlike [std::collections::hash_map::DefaultHasher]
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    /// hashes `bytes` prefixed with their length, so consecutive writes can not run into each other
+    fn write(&mut self, bytes: &[u8]) -> &mut Self {
+        for b in (bytes.len() as u64).to_le_bytes().iter().chain(bytes) {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+        self
+    }
+}
+
+/// key of the object file compiled from `sources` with the options in `args`
+pub(crate) fn object_key(args: &Args, sources: &[Source]) -> u64 {
+    let mut hash = Fnv::new();
+    hash.write(env!("CARGO_PKG_VERSION").as_bytes())
+        .write(compiler_build().as_bytes())
+        .write(format!("{:?} {} {:?} {:?}", args.opt, args.debug_info, args.crate_type, args.target).as_bytes());
+    for source in sources {
+        // the text with all includes expanded, so changes to included files are noticed too
+        hash.write(source.source_type().to_string().as_bytes()).write(source.text().as_bytes());
+    }
+    hash.0
+}
+
+/// key of linking the object with key `object_key` into `output`, including everything else passed to the linker.
+/// `links` are the `#link`s of the sources, which are not part of their text and so not of the object key
+pub(crate) fn link_key(args: &Args, object_key: u64, links: &[String], output: &str) -> u64 {
+    let mut hash = Fnv::new();
+    hash.write(&object_key.to_le_bytes())
+        .write(output.as_bytes())
+        .write(format!("{:?} {:?} {:?} {:?} {:?}", links, args.libs, args.lib_dirs, args.linker, args.crate_type).as_bytes());
+    for object in &args.objects {
+        hash.write(object.as_bytes()).write(modified(Path::new(object)).as_bytes());
+    }
+    hash.0
+}
+
+/// changes with every build of the compiler, unlike the version, so objects compiled before a change to it are not reused
+fn compiler_build() -> String {
+    env::current_exe().map(|exe| modified(&exe)).unwrap_or_default()
+}
+
+/// modification time of `path` as text, empty if it can not be read
+fn modified(path: &Path) -> String {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|time| time.as_nanos().to_string())
+        .unwrap_or_default()
+}
+
+/// A directory of object files keyed by [object_key] and stamps of linked outputs keyed by [link_key].
+/// Nothing is ever removed, deleting the directory is always safe.
+pub(crate) struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    pub(crate) fn new(dir: &str) -> Self {
+        Self { dir: PathBuf::from(dir) }
+    }
+
+    /// where the object for `key` is cached
+    pub(crate) fn object(&self, key: u64) -> PathBuf {
+        self.dir.join(format!("{key:016x}.o"))
+    }
+
+    /// lets `write` create a file at a temporary path and moves it to `path`, so no half written file is ever cached
+    pub(crate) fn store(&self, path: &Path, write: impl FnOnce(&str) -> Result<(), ParseError>) -> Result<(), ParseError> {
+        fs::create_dir_all(&self.dir)?;
+        let tmp = path.with_extension("tmp");
+        write(&tmp.to_string_lossy())?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    fn stamp(&self, key: u64) -> PathBuf {
+        self.dir.join(format!("{key:016x}.linked"))
+    }
+
+    /// whether `output` is still the file linked with `key`
+    pub(crate) fn is_linked(&self, key: u64, output: &str) -> bool {
+        let output = modified(Path::new(output));
+        !output.is_empty() && fs::read_to_string(self.stamp(key)).map_or(false, |stamp| stamp == output)
+    }
+
+    pub(crate) fn mark_linked(&self, key: u64, output: &str) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.stamp(key), modified(Path::new(output)))
+    }
+}
@@ -0,0 +1,371 @@
+use std::env::consts::{DLL_PREFIX, DLL_SUFFIX, EXE_SUFFIX};
+use std::path::Path;
+use crate::{driver, golden, repl, timing, watch};
+use crate::cache::CACHE_DIR;
+use crate::manifest::{Manifest, MANIFEST_FILE, Profile};
+use crate::optimize::OptLevel;
+use crate::target::TargetSpec;
+
+pub(crate) const USAGE: &str = "usage: minimal_language <build|run|check> [<file.mi>|-|-e <code>] [<file.mi>...] [<objects>...] [-o <output>] [-O<level>] [-g|-g0] [--release]
+                        [--profile <name>] [--emit <kinds>] [--crate-type <type>] [-I <dir>]... [-l <lib>]... [-L <dir>]...
+                        [--linker <linker>] [--cache-dir <dir>] [--no-cache] [--time-passes] [--watch]
+                        [--target <triple>] [--cpu <cpu>] [--features <features>] [-- <program args>...]
+       minimal_language repl
+       minimal_language test [<dir>] [--bless]
+
+every file is compiled into its own module and can call the fns of the others.
+`-` reads the first file from stdin, `-e <code>` compiles <code> instead of a first file.
+when no file is given, the entry, sources, output, include directories and libraries are read from mi.toml
+in the working directory
+
+commands:
+    build    compile the program to an executable
+    run      compile the program and execute it in memory, passing the arguments after `--` to its main
+    check    only tokenize and compile the program, without emitting anything
+    repl     read declarations and statements from stdin and run them immediately
+    test     build and run every .mi file in <dir> (defaults to testing), and run it again with run, comparing
+             stdout and exit code to the .expected file next to it. files in <dir>/compile-fail have to fail
+             to compile with the error given by their `//~ ERROR <category>: <message>` comment, on that line.
+             a subdirectory of <dir> with a main.mi is built from all its .mi files as one program
+
+options:
+    -e <code>            compile <code> as the program, its #includes are relative to the working directory.
+                         further files are compiled along with it
+    -o <output>          path of the produced file, or the stem of all produced files when emitting several kinds.
+                         `-o -` writes textual llvm ir to stdout
+    -O<level>            optimization level, one of 0, 1, 2, 3, s, z. overrides the profile, `-O` means `-O2`
+    -g                   emit debug info, regardless of the profile
+    -g0                  emit no debug info, regardless of the profile
+    --release            use the release profile, short for `--profile release`
+    --profile <name>     profile to take the optimization level and debug info from, defaults to debug.
+                         debug is -O0 with debug info, release is -O3 without, mi.toml may change or add profiles
+    --emit <kinds>       comma separated list of ir, bc, asm, obj, exe. defaults to exe
+    --crate-type <type>  what exe in --emit produces: exe, staticlib (lib<name>.a) or cdylib (lib<name>.so).
+                         libraries have no main, their fns keep their names. defaults to exe
+    <objects>            object files and libraries (.o, .obj, .a, .lib, .so, .dylib, .dll) linked into the executable
+    -I <dir>             search <dir> for #includes and imports not found next to the including file. searched before
+                         the include directories of mi.toml, the directories in MI_PATH and the standard library
+    -l <lib>             link the library <lib>, like `#link <lib>` in the source
+    -L <dir>             add <dir> to the directories the linker searches for libraries
+    --linker <linker>    program used for linking, defaults to the first of cc, clang, gcc in PATH
+    --cache-dir <dir>    where build keeps the objects and link stamps it reuses when neither the sources (with includes)
+                         nor the options changed. only used when emitting just obj and exe. defaults to .mi-cache
+    --no-cache           always compile and link everything
+    --time-passes        print the time spent in each phase and what it produced to stderr
+    --watch              build or run again whenever an input file or a file it #includes changes, until interrupted
+    --target <triple>    target triple to generate code for, e.g. aarch64-linux-gnu. defaults to the host. build only
+    --cpu <cpu>          cpu to generate code for, `native` for the host cpu. defaults to the host cpu,
+                         or generic when a --target is given. build only
+    --features <list>    llvm target features, e.g. +avx2,-sse4.1. build only
+    --bless              let test write the .expected files from the current output instead of comparing";
+
+/// extensions of positional arguments passed to the linker instead of being compiled
+const OBJECT_EXTENSIONS: [&str; 7] = [".o", ".obj", ".a", ".lib", ".so", ".dylib", ".dll"];
+
+/// Runs the command line interface with `args`, without the name of the program, and returns the exit code.
+/// Errors are printed to stderr.
+pub fn run_cli(args: impl Iterator<Item=String>) -> i32 {
+    let args = match Args::parse(args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            return 2
+        }
+    };
+    if args.time_passes {
+        timing::enable()
+    }
+    let result = match args.command {
+        Subcommand::Build if args.watch => watch::watch(&args, |args| driver::build(args).map(|_| 0)),
+        Subcommand::Run if args.watch => watch::watch(&args, driver::run),
+        Subcommand::Build => driver::build(&args).map(|_| 0),
+        Subcommand::Check => driver::check(&args).map(|_| 0),
+        Subcommand::Run => driver::run(&args),
+        Subcommand::Repl => repl::repl().map(|_| 0),
+        Subcommand::Test => golden::test(&args).map(|_| 0),
+    };
+    timing::report();
+    result.unwrap_or_else(|e| {
+        eprintln!("{e}");
+        1
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Subcommand {
+    Build,
+    Run,
+    Check,
+    Repl,
+    Test,
+}
+
+/// what build produces, emitted in this order, so the object already exists when the executable is linked from it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Emit {
+    Ir,
+    Bc,
+    Asm,
+    Obj,
+    Exe,
+}
+
+impl Emit {
+    fn parse(kind: &str) -> Result<Self, String> {
+        match kind {
+            "ir" | "llvm-ir" => Ok(Emit::Ir),
+            "bc" => Ok(Emit::Bc),
+            "asm" => Ok(Emit::Asm),
+            "obj" => Ok(Emit::Obj),
+            "exe" => Ok(Emit::Exe),
+            k => Err(format!("unknown emit kind: {k}"))
+        }
+    }
+
+    pub(crate) fn extension(&self) -> &'static str {
+        match self {
+            Emit::Ir => ".ll",
+            Emit::Bc => ".bc",
+            Emit::Asm => ".s",
+            Emit::Obj => ".o",
+            Emit::Exe => EXE_SUFFIX,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum CrateType {
+    Exe,
+    Staticlib,
+    Cdylib,
+}
+
+impl CrateType {
+    fn parse(ty: &str) -> Result<Self, String> {
+        match ty {
+            "exe" | "bin" => Ok(CrateType::Exe),
+            "staticlib" => Ok(CrateType::Staticlib),
+            "cdylib" => Ok(CrateType::Cdylib),
+            t => Err(format!("unknown crate type: {t}"))
+        }
+    }
+
+    /// prefix and suffix of the file name of the linked output
+    fn affixes(&self) -> (&'static str, &'static str) {
+        match self {
+            CrateType::Exe => ("", EXE_SUFFIX),
+            CrateType::Staticlib if cfg!(windows) => ("", ".lib"),
+            CrateType::Staticlib => ("lib", ".a"),
+            CrateType::Cdylib => (DLL_PREFIX, DLL_SUFFIX),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Args {
+    pub(crate) command: Subcommand,
+    pub(crate) input: String,
+    pub(crate) extra_inputs: Vec<String>,
+    /// the program given with `-e`, compiled in place of `input`
+    pub(crate) code: Option<String>,
+    pub(crate) output: Option<String>,
+    pub(crate) emit: Vec<Emit>,
+    pub(crate) crate_type: CrateType,
+    pub(crate) opt: OptLevel,
+    pub(crate) debug_info: bool,
+    pub(crate) include_dirs: Vec<String>,
+    pub(crate) libs: Vec<String>,
+    pub(crate) lib_dirs: Vec<String>,
+    pub(crate) objects: Vec<String>,
+    pub(crate) linker: Option<String>,
+    pub(crate) cache_dir: Option<String>,
+    pub(crate) target: TargetSpec,
+    pub(crate) bless: bool,
+    pub(crate) time_passes: bool,
+    pub(crate) watch: bool,
+    pub(crate) program_args: Vec<String>,
+}
+
+impl Args {
+    pub(crate) fn parse(mut args: impl Iterator<Item=String>) -> Result<Self, String> {
+        let command = match args.next().as_deref() {
+            Some("build") => Subcommand::Build,
+            Some("run") => Subcommand::Run,
+            Some("check") => Subcommand::Check,
+            Some("repl") => Subcommand::Repl,
+            Some("test") => Subcommand::Test,
+            Some(c) => return Err(format!("unknown command: {c}")),
+            None => return Err(format!("no command given"))
+        };
+        let mut input = None;
+        let mut code = None;
+        let mut output = None;
+        let mut emit = vec![];
+        let mut crate_type = CrateType::Exe;
+        let mut opt = None;
+        let mut debug_info = None;
+        let mut profile = None;
+        let mut bless = false;
+        let mut time_passes = false;
+        let mut watch = false;
+        let mut libs = vec![];
+        let mut lib_dirs = vec![];
+        let mut include_dirs = vec![];
+        let mut objects = vec![];
+        let mut program_args = vec![];
+        let mut extra_inputs = vec![];
+        let mut linker = None;
+        let mut cache_dir = Some(CACHE_DIR.to_string());
+        let mut target = TargetSpec::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-o" => output = Some(args.next().ok_or(format!("expected path after -o"))?),
+                a if a.starts_with("-O") => opt = Some(OptLevel::parse(a.split_at(2).1)
+                    .ok_or(format!("unknown optimization level: {a}"))?),
+                "-g" => debug_info = Some(true),
+                "-g0" => debug_info = Some(false),
+                "-e" if matches!(command, Subcommand::Build | Subcommand::Check | Subcommand::Run) =>
+                    code = Some(args.next().ok_or(format!("expected code after -e"))?),
+                "--bless" => bless = true,
+                "--time-passes" => time_passes = true,
+                "--watch" if matches!(command, Subcommand::Build | Subcommand::Run) => watch = true,
+                "--" if command == Subcommand::Run => program_args.extend(&mut args),
+                "-l" => libs.push(args.next().ok_or(format!("expected library after -l"))?),
+                a if a.starts_with("-l") => libs.push(a.split_at(2).1.to_string()),
+                "-I" => include_dirs.push(args.next().ok_or(format!("expected directory after -I"))?),
+                a if a.starts_with("-I") => include_dirs.push(a.split_at(2).1.to_string()),
+                "-L" => lib_dirs.push(args.next().ok_or(format!("expected directory after -L"))?),
+                a if a.starts_with("-L") => lib_dirs.push(a.split_at(2).1.to_string()),
+                "--release" => profile = Some("release".to_string()),
+                "--profile" => profile = Some(args.next().ok_or(format!("expected name after --profile"))?),
+                a if a.starts_with("--profile=") => profile = Some(a.split_at(10).1.to_string()),
+                a if a.starts_with("-o") => output = Some(a.split_at(2).1.to_string()),
+                "--emit" => emit.push(args.next().ok_or(format!("expected kinds after --emit"))?),
+                a if a.starts_with("--emit=") => emit.push(a.split_at(7).1.to_string()),
+                "--crate-type" => crate_type = CrateType::parse(&args.next().ok_or(format!("expected type after --crate-type"))?)?,
+                a if a.starts_with("--crate-type=") => crate_type = CrateType::parse(a.split_at(13).1)?,
+                "--cache-dir" => cache_dir = Some(args.next().ok_or(format!("expected directory after --cache-dir"))?),
+                a if a.starts_with("--cache-dir=") => cache_dir = Some(a.split_at(12).1.to_string()),
+                "--no-cache" => cache_dir = None,
+                "--linker" => linker = Some(args.next().ok_or(format!("expected program after --linker"))?),
+                a if a.starts_with("--linker=") => linker = Some(a.split_at(9).1.to_string()),
+                "--target" => target.triple = Some(args.next().ok_or(format!("expected triple after --target"))?),
+                a if a.starts_with("--target=") => target.triple = Some(a.split_at(9).1.to_string()),
+                "--cpu" => target.cpu = Some(args.next().ok_or(format!("expected cpu after --cpu"))?),
+                a if a.starts_with("--cpu=") => target.cpu = Some(a.split_at(6).1.to_string()),
+                "--features" => target.features = Some(args.next().ok_or(format!("expected features after --features"))?),
+                a if a.starts_with("--features=") => target.features = Some(a.split_at(11).1.to_string()),
+                a if a.starts_with("-") && a != "-" => return Err(format!("unknown option: {a}")),
+                a if OBJECT_EXTENSIONS.iter().any(|ext| a.ends_with(ext)) => objects.push(arg),
+                _ if (input.is_some() || code.is_some()) && matches!(command, Subcommand::Build | Subcommand::Check | Subcommand::Run) => extra_inputs.push(arg),
+                _ if input.is_some() => return Err(format!("unexpected argument: {arg}")),
+                _ => input = Some(arg)
+            }
+        }
+        let mut emit = emit.iter()
+            .flat_map(|kinds| kinds.split(','))
+            .map(Emit::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        emit.sort();
+        emit.dedup();
+        if emit.is_empty() {
+            emit.push(Emit::Exe)
+        }
+        if output.as_deref() == Some("-") && emit != [Emit::Ir] {
+            return Err(format!("`-o -` is only supported with --emit=ir"))
+        }
+        if matches!(command, Subcommand::Run | Subcommand::Test) && crate_type != CrateType::Exe {
+            return Err(format!("only executables can be run"))
+        }
+        if command == Subcommand::Run && target.triple.is_some() {
+            return Err(format!("run always executes on the host, --target is only supported by build"))
+        }
+        if command == Subcommand::Run && (target.cpu.is_some() || target.features.is_some()) {
+            return Err(format!("run always executes on the host cpu, --cpu and --features are only supported by build"))
+        }
+        if code.is_some() {
+            extra_inputs.splice(0..0, input.take());
+        }
+        if extra_inputs.iter().any(|i| i == "-") {
+            return Err(format!("only the first file can be read from stdin"))
+        }
+        if watch && (code.is_some() || input.as_deref() == Some("-")) {
+            return Err(format!("--watch needs files to watch, not - or -e"))
+        }
+        let manifest = match input {
+            None if code.is_none() && command != Subcommand::Repl && command != Subcommand::Test => {
+                if !Path::new(MANIFEST_FILE).is_file() {
+                    return Err(format!("no input file given and no {MANIFEST_FILE} found"))
+                }
+                Some(Manifest::load(MANIFEST_FILE)?)
+            }
+            _ => None
+        };
+        let profile_name = profile.unwrap_or("debug".to_string());
+        let profile = match &manifest {
+            Some(manifest) => manifest.profiles.get(&profile_name).cloned(),
+            None => Profile::defaults().remove(&profile_name)
+        }.ok_or(format!("unknown profile: {profile_name}"))?;
+        Ok(Self {
+            command,
+            input: match (input, &manifest) {
+                (Some(input), _) => input,
+                (None, Some(manifest)) => manifest.entry.clone(),
+                (None, None) if command == Subcommand::Test => "testing".to_string(),
+                (None, None) => String::new()
+            },
+            code,
+            extra_inputs: extra_inputs.into_iter().chain(manifest.as_ref().map(|m| m.sources.clone()).unwrap_or_default()).collect(),
+            output: output.or(manifest.as_ref().and_then(|m| m.output.clone())),
+            emit,
+            crate_type,
+            opt: opt.unwrap_or(profile.opt),
+            debug_info: debug_info.unwrap_or(profile.debug_info),
+            include_dirs: include_dirs.into_iter().chain(manifest.as_ref().map(|m| m.include.clone()).unwrap_or_default()).collect(),
+            libs: manifest.map(|m| m.libs).unwrap_or_default().into_iter().chain(libs).collect(),
+            lib_dirs,
+            objects,
+            linker,
+            cache_dir,
+            target,
+            bless,
+            time_passes,
+            watch,
+            program_args,
+        })
+    }
+
+    /// `-o` if it is the only thing emitted, otherwise the `-o` or input stem with the extension of `emit`
+    pub(crate) fn output_path(&self, emit: Emit) -> String {
+        if let (Some(output), [only]) = (&self.output, self.emit.as_slice()) {
+            if *only == emit {
+                return output.clone()
+            }
+        }
+        let stem = match &self.output {
+            Some(output) => output.rsplit_once('.')
+                .filter(|(_, ext)| !ext.contains(['/', '\\']))
+                .map(|(stem, _)| stem)
+                .unwrap_or(output),
+            // programs from stdin and -e have no name to take the stem from
+            None if self.input == "-" || self.code.is_some() => "a",
+            None => self.input.strip_suffix(".mi").unwrap_or(&self.input)
+        };
+        let path = match emit {
+            Emit::Exe => {
+                let (prefix, suffix) = self.crate_type.affixes();
+                let (dir, file) = stem.rsplit_once(['/', '\\']).map(|(d, f)| (&stem[..=d.len()], f)).unwrap_or(("", stem));
+                format!("{dir}{prefix}{file}{suffix}")
+            }
+            _ => format!("{stem}{}", emit.extension())
+        };
+        // never overwrite the source itself
+        if path == self.input { format!("{path}.out") } else { path }
+    }
+}
+
+/// name of the module compiled from the file at `path`, without directories and the `.mi` extension
+pub(crate) fn module_name(path: &str) -> String {
+    let file = path.rsplit(['/', '\\']).next().unwrap();
+    file.strip_suffix(".mi").unwrap_or(file).to_string()
+}
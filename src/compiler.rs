@@ -1,10 +1,14 @@
 use std::collections::HashMap;
 use std::env::var;
 use std::ffi::{c_uint, c_ulonglong};
-use llvm_sys::{core, LLVMIntPredicate, prelude};
+use std::path::PathBuf;
+use llvm_sys::{analysis, core, LLVMIntPredicate, LLVMLinkage, LLVMTypeKind, LLVMUnnamedAddr, prelude};
+use llvm_sys::analysis::LLVMVerifierFailureAction;
 use llvm_sys::prelude::{LLVMBool, LLVMTypeRef, LLVMValueRef};
 use crate::{c_str, c_str_ptr};
-use crate::source::{ParseError, ParseET, Span};
+use crate::debug_info::{DebugInfo, set_location};
+use crate::source::{OnParseErr, ParseError, ParseET, Span};
+use crate::target::llvm_message;
 use crate::tokens::tok_iter::TokIter;
 use crate::tokens::tokens::{Literal, NumLit, Token, TokenType};
 
@@ -39,14 +43,195 @@ macro_rules! ident_next {
     };
 }
 
-pub(crate) fn compile(mut tokens: TokIter, name: &str) -> Result<prelude::LLVMModuleRef, ParseError> {
+/// What a unit does with the fns of the files it `#include`s, which other units of the program may include as well.
+pub(crate) enum IncludedFns {
+    /// define them like the unit's own fns
+    Define,
+    /// define them in every unit of an executable, linking keeps one of them
+    LinkOnce,
+    /// only declare the fns of these files, given as canonical paths, because an earlier unit defines them
+    Declare(Vec<PathBuf>),
+}
+
+/// Compiles a whole program into a new module, with `imports` (from [declare_fns] of other files) declared in it.
+/// For libraries no `main` wrapper is generated, every `fn` keeps its own name and external linkage.
+/// Files compiled as the module `namespace` only export their `pub fn`s, as `<namespace>.<name>`.
+pub(crate) fn compile(mut tokens: TokIter, name: &str, debug_info: bool, library: bool,
+                      imports: &HashMap<String, (LLVMTypeRef, LLVMValueRef, bool)>, namespace: Option<&str>,
+                      included: &IncludedFns) -> Result<prelude::LLVMModuleRef, ParseError> {
     let module = unsafe { core::LLVMModuleCreateWithName(c_str_ptr!(name)) };
-    let function_name = c_str!("main");
-    let function_type = unsafe {
-        let mut param_types = [];
-        core::LLVMFunctionType(core::LLVMVoidType(), param_types.as_mut_ptr(), param_types.len() as u32, 0)
+    let di = if debug_info {
+        Some(DebugInfo::new(module, &tokens.nearest_point()?.source.source_type().to_string()))
+    } else { None };
+
+    let mut varmap = declare_all(module, imports);
+    let mut fn_spans = vec![];
+    while tokens.this().is_ok() {
+        let tok = tokens.this()?;
+        match tok.tt {
+            TokenType::Ident(ident) => match ident.as_str() {
+                "const" => compile_global_const(&mut tokens, &module, &mut varmap),
+                "extern" => compile_extern(&mut tokens, &module, &mut varmap),
+                "fn" => compile_fn(&mut tokens, &module, &mut varmap, di.as_ref(), namespace, false, included).map(|f| fn_spans.push((f, tok.loc))),
+                "pub" => skip_pub(&mut tokens).and_then(|_| compile_fn(&mut tokens, &module, &mut varmap, di.as_ref(), namespace, true, included))
+                    .map(|f| fn_spans.push((f, tok.loc))),
+                "import" => compile_import(&mut tokens, &mut varmap),
+                e => return Err(ParseET::ParseError("[const|extern|fn|pub|import]".to_string(), e.to_string()).at(tok.loc))
+            }
+            e => return Err(ParseET::ParseError("keyword".to_string(), format!("{e:?}")).at(tok.loc))
+        }?;
+    }
+
+    if !library {
+        compile_main_wrapper(module, &varmap, &mut fn_spans)?;
+    }
+    if let Some(di) = di {
+        di.finalize();
+    }
+    verify(module, &fn_spans)?;
+    Ok(module)
+}
+
+/// Generates the `i32 main(i32 argc, ptr argv)` called by the c runtime, which calls the user's `main`.
+/// The user's `main` may take `i32 argc ptr argv` or nothing and return `i32` as the exit code or nothing for 0.
+/// It is renamed to make room for the wrapper.
+fn compile_main_wrapper(module: prelude::LLVMModuleRef, varmap: &HashMap<String, (LLVMTypeRef, LLVMValueRef, bool)>,
+                        fn_spans: &mut Vec<(LLVMValueRef, Span)>) -> Result<(), ParseError> {
+    let fun = varmap.get("main")
+        .ok_or(ParseET::VariableError(format!("main")).error().when("looking for the main fn"))?;
+    let span = fn_spans.iter().find(|(f, _)| *f == fun.1).map(|(_, span)| span.clone());
+    let sig_error = |found: String| {
+        let e = ParseET::ParseError("fn main [i32] [with i32 argc ptr argv]".to_string(), found).error().when("checking the signature of main");
+        match &span {
+            Some(span) => e.at(span.clone()),
+            None => e
+        }
     };
-    let function = unsafe { core::LLVMAddFunction(module, function_name.as_ptr(), function_type) };
+    unsafe {
+        let ret_ty = ty_to_ty_str(core::LLVMGetReturnType(fun.0));
+        if ret_ty != "void" && ret_ty != "i32" {
+            return Err(sig_error(format!("return type {ret_ty}")))
+        }
+        let mut param_types = vec![std::ptr::null_mut(); core::LLVMCountParamTypes(fun.0) as usize];
+        core::LLVMGetParamTypes(fun.0, param_types.as_mut_ptr());
+        let params = param_types.iter().map(|ty| ty_to_ty_str(*ty)).collect::<Vec<_>>();
+        if !params.is_empty() && params != ["i32", "ptr"] || core::LLVMIsFunctionVarArg(fun.0) != 0 {
+            return Err(sig_error(format!("parameters [{}]", params.join(" "))))
+        }
+
+        let user_main = c_str!("__mi_main");
+        core::LLVMSetValueName2(fun.1, user_main.as_ptr(), user_main.to_bytes().len());
+        let mut wrapper_params = [core::LLVMInt32Type(), core::LLVMPointerType(core::LLVMInt8Type(), 0)];
+        let function_type = core::LLVMFunctionType(core::LLVMInt32Type(), wrapper_params.as_mut_ptr(), wrapper_params.len() as c_uint, 0);
+        let function = core::LLVMAddFunction(module, c_str_ptr!("main"), function_type);
+        let entry_block = core::LLVMAppendBasicBlock(function, c_str_ptr!("entry"));
+        let builder = core::LLVMCreateBuilder();
+        core::LLVMPositionBuilderAtEnd(builder, entry_block);
+        let mut args = if params.is_empty() { vec![] } else { vec![core::LLVMGetParam(function, 0), core::LLVMGetParam(function, 1)] };
+        let r = core::LLVMBuildCall2(builder, fun.0, fun.1, args.as_mut_ptr(), args.len() as c_uint, c_str_ptr!(""));
+        if ret_ty == "i32" {
+            core::LLVMBuildRet(builder, r);
+        } else {
+            core::LLVMBuildRet(builder, core::LLVMConstInt(core::LLVMInt32Type(), 0, 0));
+        }
+        core::LLVMDisposeBuilder(builder);
+        // problems in the wrapper come from the signature of the user's main
+        if let Some(span) = span {
+            fn_spans.push((function, span));
+        }
+    }
+    Ok(())
+}
+
+/// Runs the llvm verifier on `module`, reporting the first broken function at the span of the `fn` it was compiled from.
+/// Catches things like statements after `return` or non-void functions that do not always return.
+fn verify(module: prelude::LLVMModuleRef, fn_spans: &[(LLVMValueRef, Span)]) -> Result<(), ParseError> {
+    let mut msg = std::ptr::null_mut();
+    let broken = unsafe { analysis::LLVMVerifyModule(module, LLVMVerifierFailureAction::LLVMReturnStatusAction, &mut msg) };
+    let msg = llvm_message(msg);
+    if broken == 0 {
+        return Ok(())
+    }
+    let msg = msg.trim_end().replace('\n', "\n    ");
+    for (function, span) in fn_spans {
+        if unsafe { analysis::LLVMVerifyFunction(*function, LLVMVerifierFailureAction::LLVMReturnStatusAction) } != 0 {
+            return Err(ParseET::VerificationError(msg).at(span.clone()).when(&format!("verifying fn {}", value_name(*function))))
+        }
+    }
+    Err(ParseET::VerificationError(msg).error().when("verifying module"))
+}
+
+/// the name of the function or global `value` in its module
+fn value_name(value: LLVMValueRef) -> String {
+    let mut len = 0;
+    let name = unsafe { core::LLVMGetValueName2(value, &mut len) };
+    unsafe { String::from_utf8_lossy(std::slice::from_raw_parts(name as *const u8, len)).into_owned() }
+}
+
+/// skips the `pub` of a `pub fn`, leaving the `fn` to be compiled
+fn skip_pub(tokens: &mut TokIter) -> Result<(), ParseError> {
+    tokens.next();
+    if tokens.this()?.tt != TokenType::Ident(String::from("fn")) {
+        return Err(ParseET::ParseError("fn after pub".to_string(), format!("{:?}", tokens.this()?.tt)).at(tokens.this()?.loc))
+    }
+    Ok(())
+}
+
+/// Declares everything in `varmap`, which may belong to other modules, in `module`.
+/// Returns a varmap referring to the new declarations instead.
+pub(crate) fn declare_all(module: prelude::LLVMModuleRef, varmap: &HashMap<String, (LLVMTypeRef, LLVMValueRef, bool)>) -> HashMap<String, (LLVMTypeRef, LLVMValueRef, bool)> {
+    varmap.iter().map(|(name, &(ty, v, is_alloca))| unsafe {
+        let symbol = value_name(v);
+        let decl = if core::LLVMIsAFunction(v).is_null() {
+            core::LLVMAddGlobal(module, core::LLVMGlobalGetValueType(v), c_str_ptr!(symbol))
+        } else {
+            core::LLVMAddFunction(module, c_str_ptr!(symbol), ty)
+        };
+        (name.clone(), (ty, decl, is_alloca))
+    }).collect()
+}
+
+/// Declares every `fn` of `tokens` in `module` without compiling their bodies, so other files can call them.
+/// Of a file compiled as the module `namespace`, only the `pub fn`s are declared, as `<namespace>.<name>`.
+/// Returns the declared fns and the names of the `extern fn`s, which are not declared.
+pub(crate) fn declare_fns(mut tokens: TokIter, module: prelude::LLVMModuleRef, namespace: Option<&str>) -> Result<(HashMap<String, (LLVMTypeRef, LLVMValueRef, bool)>, Vec<String>), ParseError> {
+    let mut varmap = HashMap::new();
+    let mut externs = vec![];
+    // `fn` only ever starts a signature, so there is no need to understand the bodies in between
+    while tokens.left() > 0 {
+        let tok = tokens.this()?;
+        if tok.tt != TokenType::Ident(String::from("fn")) {
+            tokens.next();
+            continue
+        }
+        let is_extern = tokens.index > 0 && tokens.get(tokens.index - 1)?.tt == TokenType::Ident(String::from("extern"));
+        let is_pub = tokens.index > 0 && tokens.get(tokens.index - 1)?.tt == TokenType::Ident(String::from("pub"));
+        let (name, ty, args, vararg) = fn_sig(&mut tokens)?;
+        if is_extern {
+            externs.push(name);
+            continue
+        }
+        if namespace.is_some() && !is_pub {
+            continue
+        }
+        let ret_ty = ty_str_to_ty(&ty.unwrap_or("void".to_string())).e_at(tok.loc.clone())?;
+        let mut params = args.iter().map(|(t, _)| ty_str_to_ty(t.as_str())).collect::<Result<Vec<LLVMTypeRef>, _>>().e_at(tok.loc)?;
+        unsafe {
+            let fn_ty = core::LLVMFunctionType(ret_ty, params.as_mut_ptr(), params.len() as c_uint, vararg as LLVMBool);
+            let function = core::LLVMAddFunction(module, c_str_ptr!(symbol(namespace, &name)), fn_ty);
+            varmap.insert(name, (fn_ty, function, false));
+        }
+    }
+    Ok((varmap, externs))
+}
+
+/// Compiles one input of the repl into the function `__repl_<id>` of `module`.
+/// Top level `let` and `var` become globals so later inputs can still use them.
+/// Returns the name and type of the global of every expression statement that has a value.
+pub(crate) fn compile_repl(mut tokens: TokIter, module: prelude::LLVMModuleRef, id: usize,
+                           varmap: &mut HashMap<String, (LLVMTypeRef, LLVMValueRef, bool)>) -> Result<Vec<(String, LLVMTypeRef)>, ParseError> {
+    let function_type = unsafe { core::LLVMFunctionType(core::LLVMVoidType(), [].as_mut_ptr(), 0, 0) };
+    let function = unsafe { core::LLVMAddFunction(module, c_str_ptr!(format!("__repl_{id}")), function_type) };
     let entry_block = unsafe { core::LLVMAppendBasicBlock(function, c_str_ptr!("entry")) };
     let builder = unsafe {
         let b = core::LLVMCreateBuilder();
@@ -54,27 +239,75 @@ pub(crate) fn compile(mut tokens: TokIter, name: &str) -> Result<prelude::LLVMMo
         b
     };
 
-    let mut varmap = HashMap::new();
+    let mut results = vec![];
+    let mut local_varmap = HashMap::new();
+    let mut fn_spans = vec![(function, tokens.this()?.loc)];
     while tokens.this().is_ok() {
         let tok = tokens.this()?;
         match tok.tt {
             TokenType::Ident(ident) => match ident.as_str() {
-                "const" => compile_global_const(&mut tokens, &module, &builder, &mut varmap),
-                "extern" => compile_extern(&mut tokens, &module, &mut varmap),
-                "fn" => compile_fn(&mut tokens, &module, &mut varmap),
-                e => return Err(ParseET::ParseError("[const|extern|fn]".to_string(), e.to_string()).at(tok.loc))
+                "const" => compile_global_const(&mut tokens, &module, varmap),
+                "extern" => compile_extern(&mut tokens, &module, varmap),
+                "fn" => compile_fn(&mut tokens, &module, varmap, None, None, false, &IncludedFns::Define).map(|f| fn_spans.push((f, tok.loc))),
+                // there is no module around the repl, so pub makes no difference
+                "pub" => skip_pub(&mut tokens).and_then(|_| compile_fn(&mut tokens, &module, varmap, None, None, true, &IncludedFns::Define))
+                    .map(|f| fn_spans.push((f, tok.loc))),
+                "import" => return Err(ParseET::ParseError("#include".to_string(), "import, which only works in files".to_string()).at(tok.loc)),
+                "let" | "var" => compile_repl_global(&mut tokens, &module, &builder, varmap, &mut local_varmap),
+                "update" | "if" | "while" => compile_statement(&mut tokens, &module, &builder, &function, varmap, &mut local_varmap).map(|_| ()),
+                _ => {
+                    let v = compile_expression(&mut tokens, &module, &builder, varmap, &mut local_varmap, "")?;
+                    unsafe {
+                        let ty = core::LLVMTypeOf(v);
+                        if core::LLVMGetTypeKind(ty) != LLVMTypeKind::LLVMVoidTypeKind {
+                            let name = format!("__repl_{id}_{}", results.len());
+                            let global = core::LLVMAddGlobal(module, ty, c_str_ptr!(name));
+                            core::LLVMSetInitializer(global, core::LLVMConstNull(ty));
+                            core::LLVMBuildStore(builder, v, global);
+                            results.push((name, ty));
+                        }
+                    }
+                    Ok(())
+                }
             }
-            e => return Err(ParseET::ParseError("keyword".to_string(), format!("{e:?}")).at(tok.loc))
+            e => return Err(ParseET::ParseError("keyword or statement".to_string(), format!("{e:?}")).at(tok.loc))
         }?;
     }
 
     unsafe {
-        let fun = varmap.get("main").unwrap();
-        core::LLVMBuildCall2(builder, fun.0, fun.1, [].as_mut_ptr(), 0 as c_uint, c_str_ptr!(""));
         core::LLVMBuildRetVoid(builder);
-        core::LLVMDisposeBuilder(builder)
+        core::LLVMDisposeBuilder(builder);
+        // constants are private by default, but later inputs live in other modules
+        for (_, v, _) in varmap.values() {
+            if core::LLVMIsAFunction(*v).is_null() && core::LLVMGetGlobalParent(*v) == module {
+                core::LLVMSetLinkage(*v, LLVMLinkage::LLVMExternalLinkage);
+            }
+        }
     }
-    Ok(module)
+    verify(module, &fn_spans)?;
+    Ok(results)
+}
+
+fn compile_repl_global(tokens: &mut TokIter, module: &prelude::LLVMModuleRef, builder: &prelude::LLVMBuilderRef,
+                       varmap: &mut HashMap<String, (LLVMTypeRef, LLVMValueRef, bool)>,
+                       local_varmap: &mut HashMap<String, (LLVMTypeRef, LLVMValueRef, bool)>) -> Result<(), ParseError> {
+    let keyword = ident_next!(tokens, "[let|var]");
+    let ty = ty_str_to_ty(&ident_next!(tokens, "type"))?;
+    let name = ident_next!(tokens, "name");
+    if keyword == "let" {
+        expect_ident!(tokens, "be");
+    } else {
+        expect_ident!(tokens, "is");
+    }
+    let v = compile_expression(tokens, module, builder, varmap, local_varmap, "")?;
+    let global = unsafe {
+        let global = core::LLVMAddGlobal(*module, ty, c_str_ptr!(name));
+        core::LLVMSetInitializer(global, core::LLVMConstNull(ty));
+        core::LLVMBuildStore(*builder, v, global);
+        global
+    };
+    varmap.insert(name, (ty, global, true));
+    Ok(())
 }
 
 fn get_var(name: &str, loc: Span, varmap: &mut HashMap<String, (LLVMTypeRef, LLVMValueRef, bool)>, local_varmap: &mut HashMap<String, (LLVMTypeRef, LLVMValueRef, bool)>) -> Result<(LLVMTypeRef, LLVMValueRef, bool), ParseError>{
@@ -82,7 +315,7 @@ fn get_var(name: &str, loc: Span, varmap: &mut HashMap<String, (LLVMTypeRef, LLV
         .unwrap_or_else(||varmap.get(name).map(|t|t.clone()).ok_or(ParseET::VariableError(name.to_string()).at(loc)))
 }
 
-fn compile_global_const(tokens: &mut TokIter, module: &prelude::LLVMModuleRef, builder: &prelude::LLVMBuilderRef, varmap: &mut HashMap<String, (LLVMTypeRef, LLVMValueRef, bool)>) -> Result<(), ParseError>{
+fn compile_global_const(tokens: &mut TokIter, module: &prelude::LLVMModuleRef, varmap: &mut HashMap<String, (LLVMTypeRef, LLVMValueRef, bool)>) -> Result<(), ParseError>{
     expect_ident!(tokens, "const");
     let ty = ident_next!(tokens, "type");
     let name = ident_next!(tokens, "name");
@@ -98,7 +331,17 @@ fn compile_global_const(tokens: &mut TokIter, module: &prelude::LLVMModuleRef, b
         tt => Err(ParseET::ParseError("literal".to_string(), format!("{tt:?}")).at(tok.loc))
     }?;
     tokens.next();
-    let p = unsafe {core::LLVMBuildGlobalString(*builder, c_str_ptr!(val), c_str_ptr!(name))};
+    // what LLVMBuildGlobalString does, without needing a function to build in
+    let p = unsafe {
+        let init = core::LLVMConstString(c_str_ptr!(val), val.len() as c_uint, 0);
+        let global = core::LLVMAddGlobal(*module, core::LLVMTypeOf(init), c_str_ptr!(name));
+        core::LLVMSetInitializer(global, init);
+        core::LLVMSetGlobalConstant(global, 1);
+        core::LLVMSetLinkage(global, LLVMLinkage::LLVMPrivateLinkage);
+        core::LLVMSetUnnamedAddress(global, LLVMUnnamedAddr::LLVMGlobalUnnamedAddr);
+        core::LLVMSetAlignment(global, 1);
+        global
+    };
     varmap.insert(name, (unsafe{ core::LLVMPointerType(core::LLVMInt8Type(), 0) }, p, false));
     Ok(())
 }
@@ -172,6 +415,21 @@ fn ty_str_to_ty(ty: &str) -> Result<prelude::LLVMTypeRef, ParseError>{
     }
 }
 
+/// inverse of [ty_str_to_ty]
+pub(crate) fn ty_to_ty_str(ty: prelude::LLVMTypeRef) -> String {
+    unsafe {
+        match core::LLVMGetTypeKind(ty) {
+            LLVMTypeKind::LLVMVoidTypeKind => "void".to_string(),
+            LLVMTypeKind::LLVMPointerTypeKind => "ptr".to_string(),
+            LLVMTypeKind::LLVMIntegerTypeKind => match core::LLVMGetIntTypeWidth(ty) {
+                1 => "bool".to_string(),
+                w => format!("i{w}")
+            }
+            _ => format!("{:?}", core::LLVMGetTypeKind(ty))
+        }
+    }
+}
+
 fn compile_extern(tokens: &mut TokIter, module: &prelude::LLVMModuleRef, varmap: &mut HashMap<String, (LLVMTypeRef, LLVMValueRef, bool)>) -> Result<(), ParseError> {
     expect_ident!(tokens, "extern");
     let (name, ty, args, vararg) = fn_sig(tokens)?;
@@ -186,28 +444,107 @@ fn compile_extern(tokens: &mut TokIter, module: &prelude::LLVMModuleRef, varmap:
     Ok(())
 }
 
+/// the symbol of the fn `name`, prefixed with the namespace of the module it is in
+fn symbol(namespace: Option<&str>, name: &str) -> String {
+    match namespace {
+        Some(namespace) => format!("{namespace}.{name}"),
+        None => name.to_string()
+    }
+}
+
+/// Reads `import <path> [with <name>... end]`. The imported module itself is compiled by the driver,
+/// which passes its `pub fn`s in the varmap as `<namespace>.<name>`. The names after `with` can also be used without the namespace.
+fn compile_import(tokens: &mut TokIter, varmap: &mut HashMap<String, (LLVMTypeRef, LLVMValueRef, bool)>) -> Result<(), ParseError> {
+    let import = tokens.this()?;
+    expect_ident!(tokens, "import");
+    // the path is everything on the line of the import
+    let text = import.loc.source().text();
+    let mut path = String::new();
+    while tokens.left() > 0 && !text[import.loc.end..tokens.this()?.loc.start].contains('\n') && tokens.this()?.tt != TokenType::Ident(String::from("with")) {
+        let loc = tokens.this()?.loc;
+        path.push_str(&text[loc.start..=loc.end]);
+        tokens.next();
+    }
+    let namespace = path.rsplit('/').next().unwrap().to_string();
+    if tokens.left() == 0 || tokens.this()?.tt != TokenType::Ident(String::from("with")) {
+        return Ok(())
+    }
+    tokens.next();
+    loop {
+        let tok = tokens.this()?;
+        let name = ident_next!(tokens, "[<name>|end]");
+        if name == "end" {
+            break
+        }
+        let f = varmap.get(&format!("{namespace}.{name}")).cloned()
+            .ok_or(ParseET::VariableError(format!("pub fn {name} in {namespace}")).at(tok.loc.clone()).when(&format!("importing from {path}")))?;
+        if varmap.contains_key(&name) {
+            return Err(ParseET::ImportError(format!("{name} is already defined, call {namespace}.{name} instead")).at(tok.loc))
+        }
+        varmap.insert(name, f);
+    }
+    Ok(())
+}
+
+/// Reads the rest of a name qualified with a module namespace, like the `.mod` of `math.mod`, extending `loc` over it.
+/// Returns `name` itself if it is not followed by one.
+fn qualified(tokens: &mut TokIter, name: String, loc: &mut Span) -> String {
+    if tokens.left() >= 2 {
+        if let (Ok(Token { tt: TokenType::Particle('.', _), .. }), Ok(Token { tt: TokenType::Ident(member), loc: member_loc })) = (tokens.this(), tokens.get(tokens.index + 1)) {
+            loc.extend(member_loc.end());
+            tokens.index += 2;
+            return format!("{name}.{member}")
+        }
+    }
+    name
+}
+
 fn compile_fn(tokens: &mut TokIter, module: &prelude::LLVMModuleRef,
-              varmap: &mut HashMap<String, (LLVMTypeRef, LLVMValueRef, bool)>) -> Result<(), ParseError> {
+              varmap: &mut HashMap<String, (LLVMTypeRef, LLVMValueRef, bool)>, di: Option<&DebugInfo>,
+              namespace: Option<&str>, public: bool, included: &IncludedFns) -> Result<LLVMValueRef, ParseError> {
+    let fn_loc = tokens.this()?.loc;
     let (name, ty, args, vararg) = fn_sig(tokens)?;
-    let function_name = c_str!(name.as_str());
+    let function_name = c_str!(symbol(namespace, &name));
     let mut param_names = vec![];
     let mut param_types = vec![];
-    let ret_ty = ty_str_to_ty(&ty.clone().unwrap_or(String::from("void")))?;
+    let ret_ty = ty_str_to_ty(&ty.clone().unwrap_or(String::from("void"))).e_at(fn_loc.clone())?;
     let function_type = unsafe {
         for (ty, n) in args {
-            param_types.push(ty_str_to_ty(&ty).unwrap());
+            param_types.push(ty_str_to_ty(&ty).e_at(fn_loc.clone())?);
             param_names.push(n);
         }
         core::LLVMFunctionType(ret_ty, param_types.as_mut_ptr(), param_types.len() as u32, vararg as LLVMBool)
     };
+    // a fn imported with `with` is declared under the name of its module
+    if let Some((_, imported, _)) = varmap.get(&name).filter(|(_, f, _)| value_name(*f) != symbol(namespace, &name)) {
+        return Err(ParseET::ImportError(format!("fn {name} clashes with the imported {}", value_name(*imported))).at(fn_loc))
+    }
     let function = unsafe { core::LLVMAddFunction(*module, function_name.as_ptr(), function_type) };
+    // fns of modules get their own names, only those of plain files can be defined by several units
+    let included_file = fn_loc.start().included_file().filter(|_| namespace.is_none());
+    let declare_only = matches!((included, &included_file), (IncludedFns::Declare(files), Some(file)) if files.contains(file));
+    // a module's fns are private unless marked pub
+    if namespace.is_some() && !public {
+        unsafe { core::LLVMSetLinkage(function, LLVMLinkage::LLVMInternalLinkage) }
+    } else if included_file.is_some() && matches!(included, IncludedFns::LinkOnce) {
+        unsafe { core::LLVMSetLinkage(function, LLVMLinkage::LLVMLinkOnceODRLinkage) }
+    }
+    // the body of a fn that is only declared is still checked, in a function that is thrown away afterwards
+    let body = if declare_only {
+        unsafe { core::LLVMAddFunction(*module, c_str_ptr!(""), function_type) }
+    } else {
+        function
+    };
+    if let Some(di) = di.filter(|_| !declare_only) {
+        di.function(function, &name, &fn_loc);
+    }
     varmap.insert(name.clone(), (function_type, function, false));
     let mut local_varmap = HashMap::new();
     for (i, pn) in param_names.into_iter().enumerate() {
-        let v = unsafe { core::LLVMGetParam(function, i as c_uint) };
+        let v = unsafe { core::LLVMGetParam(body, i as c_uint) };
         local_varmap.insert(pn, (param_types.remove(0), v, false));
     }
-    let entry_block = unsafe { core::LLVMAppendBasicBlock(function, c_str_ptr!("entry")) };
+    let entry_block = unsafe { core::LLVMAppendBasicBlock(body, c_str_ptr!("entry")) };
     let builder = unsafe {
         let b = core::LLVMCreateBuilder();
         core::LLVMPositionBuilderAtEnd(b, entry_block);
@@ -216,20 +553,31 @@ fn compile_fn(tokens: &mut TokIter, module: &prelude::LLVMModuleRef,
 
     unsafe {
         while tokens.this()?.tt != TokenType::Ident(String::from("end")){
-            compile_statement(tokens, module, &builder, &function, varmap, &mut local_varmap)?;
+            compile_statement(tokens, module, &builder, &body, varmap, &mut local_varmap)?;
         }
-        if let None = ty {
-            core::LLVMBuildRetVoid(builder);
+        let block = core::LLVMGetInsertBlock(builder);
+        if core::LLVMGetBasicBlockTerminator(block).is_null() {
+            if let None = ty {
+                core::LLVMBuildRetVoid(builder);
+            } else if block != entry_block && core::LLVMGetFirstUse(core::LLVMBasicBlockAsValue(block)).is_null() {
+                // e.g. the continuation of an if where every branch returns
+                core::LLVMBuildUnreachable(builder);
+            }
+            // otherwise the function can reach its end without returning, which the verifier reports
         }
         core::LLVMDisposeBuilder(builder);
+        if declare_only {
+            core::LLVMDeleteFunction(body);
+        }
     }
     expect_ident!(tokens, "end");
-    Ok(())
+    Ok(function)
 }
 
 fn compile_statement(tokens: &mut TokIter, module: &prelude::LLVMModuleRef, builder: &prelude::LLVMBuilderRef, function: &LLVMValueRef,
                      varmap: &mut HashMap<String, (LLVMTypeRef, LLVMValueRef, bool)>,
                      local_varmap: &mut HashMap<String, (LLVMTypeRef, LLVMValueRef, bool)>) -> Result<bool, ParseError> {
+    set_location(*builder, *function, &tokens.this()?.loc);
     match ident_next!(tokens, "[let|<expr>]").as_str() {
         "var" => compile_var_create(tokens, module, builder, varmap, local_varmap)?,
         "update" => compile_var_update(tokens, module, builder, varmap, local_varmap)?,
@@ -253,7 +601,9 @@ fn compile_expression(tokens: &mut TokIter, module: &prelude::LLVMModuleRef, bui
         "call" => compile_fn_call(tokens, module, builder, varmap, local_varmap, ret_name)?,
         "literal" => compile_literal(tokens, module, builder, varmap, local_varmap)?,
         v => {
-            let (ty, v, is_alloca) = get_var(v, tokens.this()?.loc, varmap, local_varmap)?;
+            let mut loc = tokens.get(tokens.index - 1)?.loc;
+            let name = qualified(tokens, v.to_string(), &mut loc);
+            let (ty, v, is_alloca) = get_var(&name, loc, varmap, local_varmap)?;
             if is_alloca {
                 unsafe { core::LLVMBuildLoad2(*builder, ty, v, c_str_ptr!("")) }
             } else { v }
@@ -377,7 +727,7 @@ fn compile_fn_call(tokens: &mut TokIter, module: &prelude::LLVMModuleRef, builde
                     varmap: &mut HashMap<String, (LLVMTypeRef, LLVMValueRef, bool)>,
                     local_varmap: &mut HashMap<String, (LLVMTypeRef, LLVMValueRef, bool)>,
                     ret_name: &str) -> Result<LLVMValueRef, ParseError> {
-    let name_tt = tokens.this()?.tt;
+    let Token { tt: name_tt, loc: mut name_loc } = tokens.this()?;
     let name = if let TokenType::Particle(p, _) = name_tt {
         let mut op = p.to_string();
         tokens.next();
@@ -387,7 +737,8 @@ fn compile_fn_call(tokens: &mut TokIter, module: &prelude::LLVMModuleRef, builde
         }
         op
     } else {
-        ident_next!(tokens, "name")
+        let name = ident_next!(tokens, "name");
+        qualified(tokens, name, &mut name_loc)
     };
     let n = ident_next!(tokens, "[with|end]");
     let mut args = vec![];
@@ -402,8 +753,12 @@ fn compile_fn_call(tokens: &mut TokIter, module: &prelude::LLVMModuleRef, builde
         } {}
     }
     let r = if let TokenType::Particle(p, _) = name_tt{
-        let b = args.pop().expect(&format!("no arg 1 for bin op {name}"));
-        let a = args.pop().expect(&format!("no arg 2 for binary op {name}"));
+        if args.len() != 2 {
+            return Err(ParseET::ParseError("2 arguments".to_string(), format!("{}", args.len()))
+                .at(name_loc).when(&format!("compiling binary op {name}")))
+        }
+        let b = args.pop().unwrap();
+        let a = args.pop().unwrap();
         unsafe {
             match name.as_str() {
                 "+" => core::LLVMBuildAdd(*builder, a, b, c_str_ptr!(ret_name)),
@@ -419,11 +774,11 @@ fn compile_fn_call(tokens: &mut TokIter, module: &prelude::LLVMModuleRef, builde
                 "<=" => core::LLVMBuildICmp(*builder, LLVMIntPredicate::LLVMIntSLE, a, b, c_str_ptr!(ret_name)),
                 "==" => core::LLVMBuildICmp(*builder, LLVMIntPredicate::LLVMIntEQ, a, b, c_str_ptr!(ret_name)),
                 "!=" => core::LLVMBuildICmp(*builder, LLVMIntPredicate::LLVMIntNE, a, b, c_str_ptr!(ret_name)),
-                c => unreachable!("unknown literal func {c}")
+                c => return Err(ParseET::VariableError(format!("unknown operator {c}")).at(name_loc))
             }
         }
     } else {
-        let fun = get_var(&name, tokens.this()?.loc, varmap, local_varmap)?;
+        let fun = get_var(&name, name_loc, varmap, local_varmap)?;
         unsafe { core::LLVMBuildCall2(*builder, fun.0, fun.1, args.as_mut_ptr(), args.len() as c_uint, c_str_ptr!(ret_name)) }
     };
     Ok(r)
@@ -435,7 +790,9 @@ fn compile_literal(tokens: &mut TokIter, module: &prelude::LLVMModuleRef, builde
     let ty = ty_str_to_ty(&ident_next!(tokens, "type"))?;
     let (value, loc) = if let Token { tt: TokenType::Literal(lit), loc} = tokens.this()? {
         (lit, loc)
-    } else { panic!("literal value is not a literal value") };
+    } else {
+        return Err(ParseET::ParseError("literal".to_string(), format!("{:?}", tokens.this()?.tt)).at(tokens.this()?.loc))
+    };
     tokens.next();
     let v = unsafe {
         match value {
@@ -482,7 +839,7 @@ fn compile_var_update(tokens: &mut TokIter, module: &prelude::LLVMModuleRef, bui
 varmap: &mut HashMap<String, (LLVMTypeRef, LLVMValueRef, bool)>,
 local_varmap: &mut HashMap<String, (LLVMTypeRef, LLVMValueRef, bool)>) -> Result<(), ParseError> {
     let name = ident_next!(tokens, "name");
-    let (ty, alloc_v, _true) = get_var(&name, tokens.this()?.loc, varmap, local_varmap)?;
+    let (ty, alloc_v, _true) = get_var(&name, tokens.get(tokens.index - 1)?.loc, varmap, local_varmap)?;
     expect_ident!(tokens, "to");
     let v = compile_expression(tokens, module, builder, varmap, local_varmap, &name)?;
     unsafe {core::LLVMBuildStore(*builder, v, alloc_v);}
@@ -0,0 +1,96 @@
+use std::cell::RefCell;
+use std::ffi::c_char;
+use std::ptr;
+use llvm_sys::{core, debuginfo, LLVMModuleFlagBehavior};
+use llvm_sys::debuginfo::{LLVMDIFlagZero, LLVMDWARFEmissionKind, LLVMDWARFSourceLanguage};
+use llvm_sys::prelude::{LLVMBuilderRef, LLVMDIBuilderRef, LLVMMetadataRef, LLVMModuleRef, LLVMValueRef};
+use crate::source::Span;
+
+/// Line table debug info for one module: a compile unit for the source file and a subprogram for every `fn`.
+pub(crate) struct DebugInfo {
+    builder: LLVMDIBuilderRef,
+    file: LLVMMetadataRef,
+    /// the included files fns were found in, by path
+    included: RefCell<Vec<(String, LLVMMetadataRef)>>,
+}
+
+fn str_arg(s: &str) -> (*const c_char, usize) {
+    (s.as_ptr() as *const c_char, s.len())
+}
+
+fn create_file(builder: LLVMDIBuilderRef, path: &str) -> LLVMMetadataRef {
+    let (dir, name) = path.rsplit_once(['/', '\\']).unwrap_or((".", path));
+    let (name_ptr, name_len) = str_arg(name);
+    let (dir_ptr, dir_len) = str_arg(dir);
+    unsafe { debuginfo::LLVMDIBuilderCreateFile(builder, name_ptr, name_len, dir_ptr, dir_len) }
+}
+
+impl DebugInfo {
+    pub(crate) fn new(module: LLVMModuleRef, path: &str) -> Self {
+        unsafe {
+            let builder = debuginfo::LLVMCreateDIBuilder(module);
+            let file = create_file(builder, path);
+            let (producer, producer_len) = str_arg(concat!("minimal_language ", env!("CARGO_PKG_VERSION")));
+            let (empty, _) = str_arg("");
+            debuginfo::LLVMDIBuilderCreateCompileUnit(builder, LLVMDWARFSourceLanguage::LLVMDWARFSourceLanguageC, file,
+                                                      producer, producer_len, 0, empty, 0, 0, empty, 0,
+                                                      LLVMDWARFEmissionKind::LLVMDWARFEmissionKindFull,
+                                                      0, 0, 0, empty, 0, empty, 0);
+            let version = |v: u32| core::LLVMValueAsMetadata(core::LLVMConstInt(core::LLVMInt32Type(), v as u64, 0));
+            let (key, key_len) = str_arg("Debug Info Version");
+            core::LLVMAddModuleFlag(module, LLVMModuleFlagBehavior::LLVMModuleFlagBehaviorWarning, key, key_len,
+                                    version(debuginfo::LLVMDebugMetadataVersion()));
+            let (key, key_len) = str_arg("Dwarf Version");
+            core::LLVMAddModuleFlag(module, LLVMModuleFlagBehavior::LLVMModuleFlagBehaviorWarning, key, key_len, version(4));
+            Self { builder, file, included: RefCell::new(vec![]) }
+        }
+    }
+
+    /// the file `span` is in, which differs from the compile unit's for `#include`d code
+    fn file_of(&self, span: &Span) -> LLVMMetadataRef {
+        let path = span.start().file().to_string();
+        if &path == span.source().source_type().to_string().as_str() {
+            return self.file
+        }
+        let mut included = self.included.borrow_mut();
+        if let Some((_, file)) = included.iter().find(|(p, _)| *p == path) {
+            return *file
+        }
+        let file = create_file(self.builder, &path);
+        included.push((path, file));
+        file
+    }
+
+    /// attaches a subprogram to `function`, which was declared by the `fn` at `span`
+    pub(crate) fn function(&self, function: LLVMValueRef, name: &str, span: &Span) {
+        let line = span.start().pos().0 as u32;
+        let file = self.file_of(span);
+        let (name_ptr, name_len) = str_arg(name);
+        unsafe {
+            let ty = debuginfo::LLVMDIBuilderCreateSubroutineType(self.builder, file, ptr::null_mut(), 0, LLVMDIFlagZero);
+            let sp = debuginfo::LLVMDIBuilderCreateFunction(self.builder, file, name_ptr, name_len, name_ptr, name_len,
+                                                            file, line, ty, 0, 1, line, LLVMDIFlagZero, 0);
+            debuginfo::LLVMSetSubprogram(function, sp);
+        }
+    }
+
+    pub(crate) fn finalize(self) {
+        unsafe {
+            debuginfo::LLVMDIBuilderFinalize(self.builder);
+            debuginfo::LLVMDisposeDIBuilder(self.builder);
+        }
+    }
+}
+
+/// makes the following instructions of `builder` point at `span`, if `function` has debug info
+pub(crate) fn set_location(builder: LLVMBuilderRef, function: LLVMValueRef, span: &Span) {
+    unsafe {
+        let sp = debuginfo::LLVMGetSubprogram(function);
+        if sp.is_null() {
+            return
+        }
+        let (line, col) = span.start().pos();
+        let loc = debuginfo::LLVMDIBuilderCreateDebugLocation(core::LLVMGetGlobalContext(), line as u32, col as u32 + 1, sp, ptr::null_mut());
+        core::LLVMSetCurrentDebugLocation2(builder, loc);
+    }
+}
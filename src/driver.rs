@@ -0,0 +1,349 @@
+use std::fs;
+use std::env::consts::{DLL_PREFIX, DLL_SUFFIX};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use llvm_sys::{bit_writer, core, target_machine};
+use llvm_sys::prelude::{LLVMModuleRef, LLVMTypeRef, LLVMValueRef};
+use llvm_sys::target_machine::{LLVMCodeGenFileType, LLVMTargetMachineRef};
+use crate::c_str_ptr;
+use crate::cache::{self, Cache};
+use crate::cli::{Args, CrateType, Emit, module_name};
+use crate::compiler::{compile, declare_fns, IncludedFns};
+use crate::jit::Jit;
+use crate::link::{archive, find_linker, link, link_modules};
+use crate::optimize::optimize;
+use crate::source::{canonical, OnParseErr, ParseError, ParseET, Source, SourceType};
+use crate::timing;
+use crate::target::{configure_module, emit_to_file, llvm_message, native_target_machine, target_machine};
+use crate::tokens::tok_iter::TokIter;
+use crate::tokens::tokenize::tokenize;
+
+/// Reads the input, stdin for `-` or the `-e` code, and every extra input, with their includes expanded.
+/// They are followed by every file they (or the files they import) `import`, each loaded once.
+fn load_sources(args: &Args) -> Result<Vec<Source>, ParseError> {
+    let load = |read: &dyn Fn() -> Result<Source, ParseError>| -> Result<Source, ParseError> {
+        let source = timing::time("include expansion", read)?;
+        timing::count("include expansion", "files", 1);
+        timing::count("include expansion", "lines", source.text().lines().count());
+        Ok(source)
+    };
+    let entry = if args.code.is_some() { None } else { Some(&args.input) };
+    let mut sources = [entry].into_iter().chain(args.extra_inputs.iter().map(Some))
+        .map(|input| load(&|| match input {
+            None => Source::from_string_with_includes(args.code.clone().unwrap_or_default(), &args.include_dirs),
+            Some(input) if input == "-" => Source::from_stdin(&args.include_dirs),
+            Some(input) => Source::from_file_with_includes(input.clone(), &args.include_dirs)
+        }))
+        .collect::<Result<Vec<_>, _>>()?;
+    // the namespace of every imported module and the file it is compiled from
+    let mut imported = HashMap::<String, PathBuf>::new();
+    let mut i = 0;
+    while i < sources.len() {
+        for (j, import) in sources[i].imports().to_vec().iter().enumerate() {
+            let namespace = module_name(import);
+            match imported.get(&namespace) {
+                Some(file) if *file == canonical(import) => continue,
+                // their symbols would clash
+                Some(file) => return Err(sources[i].import_error(j, ParseET::ImportError(
+                    format!("module {namespace} is already imported from {}, modules need different names", file.display())))),
+                None => {
+                    imported.insert(namespace, canonical(import));
+                    let module = load(&|| sources[i].load_import(j, &args.include_dirs))?;
+                    sources.push(module);
+                }
+            }
+        }
+        i += 1;
+    }
+    Ok(sources)
+}
+
+/// every file the program is read from, including the `#include`d ones.
+/// falls back to just the inputs when they can't be loaded
+pub(crate) fn source_files(args: &Args) -> Vec<String> {
+    // loading is repeated when compiling, which reports what went wrong
+    match load_sources(args) {
+        Ok(sources) => sources.iter().flat_map(|source| source.files()).map(str::to_string).collect(),
+        Err(_) => [&args.input].into_iter().chain(&args.extra_inputs).cloned().collect()
+    }
+}
+
+/// the libraries `sources` ask for with `#link`
+fn links(sources: &[Source]) -> Vec<String> {
+    let mut links = vec![];
+    for link in sources.iter().flat_map(|source| source.links()) {
+        if !links.contains(link) {
+            links.push(link.clone())
+        }
+    }
+    links
+}
+
+/// A file compiled into its own module, with the fns other files can call.
+struct Unit {
+    name: String,
+    /// the canonical path of the file, if it is one
+    path: Option<PathBuf>,
+    /// set for files compiled because of an `import`, their `pub fn`s are called as `<namespace>.<name>`
+    namespace: Option<String>,
+    /// canonical paths of the files this one imports
+    imports: Vec<PathBuf>,
+    /// canonical paths of the files this one `#include`s
+    includes: Vec<PathBuf>,
+    tokens: TokIter,
+    fns: HashMap<String, (LLVMTypeRef, LLVMValueRef, bool)>,
+    externs: Vec<String>,
+}
+
+/// Compiles each of `sources` into its own module and links them into one.
+/// Input files can call each other's `fn`s, the `main` wrapper is generated for the file defining `main`.
+/// The `import`ed files that follow them only share their `pub fn`s, under their namespace.
+fn compile_module(args: &Args, sources: Vec<Source>) -> Result<LLVMModuleRef, ParseError> {
+    // the entry, or the -e code, and the extra inputs come before the imported files
+    let inputs = 1 + args.extra_inputs.len();
+    let mut files = vec![];
+    // a module per file, fns that several files include are declared by each of them under the same name
+    let mut declarations = vec![];
+    let declared = sources.into_iter().enumerate().try_for_each(|(i, source)| {
+        let (name, path) = match source.source_type() {
+            SourceType::File(path) => (module_name(path), Some(canonical(path))),
+            SourceType::Stdin => ("stdin".to_string(), None),
+            SourceType::String => ("string".to_string(), None),
+        };
+        let namespace = if i < inputs { None } else { Some(name.clone()) };
+        let imports = source.imports().iter().map(|import| canonical(import)).collect();
+        let includes = source.files().map(canonical).filter(|file| Some(file) != path.as_ref()).collect();
+        let tokens = timing::time("tokenize", || tokenize(source))?;
+        timing::count("tokenize", "tokens", tokens.len());
+        let tokens = TokIter::new(tokens);
+        let module = unsafe { core::LLVMModuleCreateWithName(c_str_ptr!(format!("{name}.declarations"))) };
+        declarations.push(module);
+        let (fns, externs) = declare_fns(tokens.clone(), module, namespace.as_deref())?;
+        files.push(Unit { name, path, namespace, imports, includes, tokens, fns, externs });
+        Ok(())
+    });
+    let compiled = declared.and_then(|_| {
+        let has_main = files.iter().any(|unit| unit.namespace.is_none() && unit.fns.contains_key("main"));
+        let mut modules = vec![];
+        for (i, unit) in files.iter().enumerate() {
+            let mut imports = HashMap::new();
+            if unit.namespace.is_none() {
+                imports.extend(files.iter().enumerate()
+                    .filter(|(j, other)| *j != i && other.namespace.is_none())
+                    .flat_map(|(_, other)| &other.fns)
+                    .filter(|(name, _)| !unit.fns.contains_key(*name) && !unit.externs.contains(name))
+                    .map(|(name, decl)| (name.clone(), *decl)));
+            }
+            for module in files.iter().filter(|other| other.namespace.is_some() && other.path.as_ref().is_some_and(|p| unit.imports.contains(p))) {
+                let namespace = module.namespace.as_ref().unwrap();
+                imports.extend(module.fns.iter().map(|(name, decl)| (format!("{namespace}.{name}"), *decl)));
+            }
+            // without any main, the entry reports it missing
+            let library = args.crate_type != CrateType::Exe || unit.namespace.is_some()
+                || if has_main { !unit.fns.contains_key("main") } else { i > 0 };
+            // linkonce fns would not be exported from libraries, and may even be optimized away there
+            let included = if args.crate_type == CrateType::Exe && files.len() > 1 {
+                IncludedFns::LinkOnce
+            } else {
+                IncludedFns::Declare(unit.includes.iter().filter(|file| files[..i].iter().any(|other| other.includes.contains(file))).cloned().collect())
+            };
+            match timing::time("compile", || compile(unit.tokens.clone(), &unit.name, args.debug_info, library, &imports, unit.namespace.as_deref(), &included)) {
+                Ok(module) => modules.push(module),
+                Err(e) => {
+                    modules.into_iter().for_each(|module| unsafe { core::LLVMDisposeModule(module) });
+                    return Err(e)
+                }
+            }
+        }
+        let module = modules.remove(0);
+        if !modules.is_empty() {
+            timing::time("link modules", || link_modules(module, modules)).map_err(|e| {
+                unsafe { core::LLVMDisposeModule(module) }
+                e
+            })?;
+        }
+        timing::count_module("compile", module);
+        Ok(module)
+    });
+    declarations.into_iter().for_each(|module| unsafe { core::LLVMDisposeModule(module) });
+    compiled
+}
+
+pub(crate) fn check(args: &Args) -> Result<(), ParseError> {
+    let module = compile_module(args, load_sources(args)?)?;
+    unsafe { core::LLVMDisposeModule(module) }
+    Ok(())
+}
+
+pub(crate) fn build(args: &Args) -> Result<(), ParseError> {
+    let sources = load_sources(args)?;
+    let links = links(&sources);
+    // only objects and what is linked from them can be reused
+    if let Some(cache) = args.cache_dir.as_ref().filter(|_| args.emit.iter().all(|e| matches!(e, Emit::Obj | Emit::Exe))) {
+        let cache = Cache::new(cache);
+        let key = cache::object_key(args, &sources);
+        let object = cache.object(key);
+        if !object.is_file() {
+            let path = object.to_string_lossy().into_owned();
+            codegen(args, sources, |module, tm| timing::time("emit obj", || cache.store(&object, |tmp|
+                emit_to_file(tm, module, tmp, LLVMCodeGenFileType::LLVMObjectFile))).e_when(format!("emitting {path}")))?;
+        }
+        return emit_from_object(args, &object, &links, &cache, key)
+    }
+    codegen(args, sources, |module, tm| emit(args, module, tm, &links))
+}
+
+/// compiles and optimizes `sources` for the target, then passes the module to `emit`
+fn codegen(args: &Args, sources: Vec<Source>, emit: impl FnOnce(LLVMModuleRef, LLVMTargetMachineRef) -> Result<(), ParseError>) -> Result<(), ParseError> {
+    let module = compile_module(args, sources)?;
+    let r = target_machine(&args.target, args.opt).and_then(|tm| {
+        configure_module(module, tm);
+        let r = optimize_module(args, module, tm).and_then(|_| emit(module, tm));
+        unsafe { target_machine::LLVMDisposeTargetMachine(tm) }
+        r
+    });
+    unsafe { core::LLVMDisposeModule(module) }
+    r
+}
+
+/// emits the object and executable from an already generated `object`, skipping the linker if the output is up to date
+fn emit_from_object(args: &Args, object: &Path, links: &[String], cache: &Cache, key: u64) -> Result<(), ParseError> {
+    args.emit.iter().try_for_each(|&kind| {
+        let path = args.output_path(kind);
+        match kind {
+            Emit::Obj => fs::copy(object, &path).map(|_| ()).map_err(ParseError::from),
+            _ => {
+                let key = cache::link_key(args, key, links, &path);
+                if cache.is_linked(key, &path) {
+                    return Ok(())
+                }
+                timing::time("link", || link_output(args, &object.to_string_lossy(), links, &path))?;
+                cache.mark_linked(key, &path).map_err(ParseError::from)
+            }
+        }.e_when(format!("emitting {path}"))
+    })
+}
+
+fn emit(args: &Args, module: LLVMModuleRef, tm: LLVMTargetMachineRef, links: &[String]) -> Result<(), ParseError> {
+    args.emit.iter().try_for_each(|&kind| {
+        let path = args.output_path(kind);
+        match kind {
+            Emit::Ir => timing::time("emit ir", || emit_ir(module, &path)),
+            Emit::Bc => timing::time("emit bc", || emit_bitcode(module, &path)),
+            Emit::Asm => timing::time("emit asm", || emit_to_file(tm, module, &path, LLVMCodeGenFileType::LLVMAssemblyFile)),
+            Emit::Obj => timing::time("emit obj", || emit_to_file(tm, module, &path, LLVMCodeGenFileType::LLVMObjectFile)),
+            Emit::Exe => {
+                let object_file = args.output_path(Emit::Obj);
+                // reuse the object file if it is emitted anyway
+                let keep_object = args.emit.contains(&Emit::Obj);
+                if !keep_object {
+                    timing::time("emit obj", || emit_to_file(tm, module, &object_file, LLVMCodeGenFileType::LLVMObjectFile))?;
+                }
+                let linked = timing::time("link", || link_output(args, &object_file, links, &path));
+                if !keep_object {
+                    fs::remove_file(&object_file)?;
+                }
+                linked
+            }
+        }.e_when(format!("emitting {path}"))
+    })
+}
+
+fn optimize_module(args: &Args, module: LLVMModuleRef, tm: LLVMTargetMachineRef) -> Result<(), ParseError> {
+    timing::time("optimize", || optimize(module, tm, args.opt))?;
+    timing::count_module("optimize", module);
+    Ok(())
+}
+
+/// links or archives `object` and the extra objects into `path`, depending on the crate type
+fn link_output(args: &Args, object: &str, links: &[String], path: &str) -> Result<(), ParseError> {
+    let objects = [object.to_string()].into_iter().chain(args.objects.iter().cloned()).collect::<Vec<_>>();
+    let libs = links.iter().chain(&args.libs).cloned().collect::<Vec<_>>();
+    match args.crate_type {
+        // libraries requested by the source are left to whoever links the archive
+        CrateType::Staticlib => archive(&objects, path),
+        crate_type => find_linker(args.linker.as_deref()).and_then(|linker|
+            link(&linker, &objects, &args.lib_dirs, &libs, crate_type == CrateType::Cdylib, path))
+    }
+}
+
+/// writes textual llvm ir to `path`, or to stdout if `path` is `-`
+fn emit_ir(module: LLVMModuleRef, path: &str) -> Result<(), ParseError> {
+    if path == "-" {
+        let ir = llvm_message(unsafe { core::LLVMPrintModuleToString(module) });
+        print!("{ir}");
+        return Ok(())
+    }
+    let mut err = ptr::null_mut();
+    if unsafe { core::LLVMPrintModuleToFile(module, c_str_ptr!(path), &mut err) } != 0 {
+        return Err(ParseET::CodegenError(llvm_message(err)).error())
+    }
+    Ok(())
+}
+
+fn emit_bitcode(module: LLVMModuleRef, path: &str) -> Result<(), ParseError> {
+    if unsafe { bit_writer::LLVMWriteBitcodeToFile(module, c_str_ptr!(path)) } != 0 {
+        return Err(ParseET::CodegenError(format!("could not write bitcode")).error())
+    }
+    Ok(())
+}
+
+/// compiles the program and executes it in the jit, returning its exit code.
+/// the objects and libraries build would link are loaded into the jit, other extern fns are resolved against the compiler process
+pub(crate) fn run(args: &Args) -> Result<i32, ParseError> {
+    let sources = load_sources(args)?;
+    let links = links(&sources);
+    // the program name, `<stdin>` or `<string>` when it has no file
+    let program = sources[0].source_type().to_string();
+    let module = compile_module(args, sources)?;
+    let added = native_target_machine(args.opt).and_then(|tm| {
+        configure_module(module, tm);
+        let r = optimize_module(args, module, tm);
+        unsafe { target_machine::LLVMDisposeTargetMachine(tm) }
+        r
+    }).and_then(|_| timing::time("jit", || {
+        let mut jit = Jit::new()?;
+        load_native(&mut jit, args, &links)?;
+        jit.add_module(module)?;
+        Ok(jit)
+    }));
+    unsafe { core::LLVMDisposeModule(module) }
+    let argv = [program].into_iter().chain(args.program_args.iter().cloned()).collect::<Vec<_>>();
+    added?.run_main(&argv)
+}
+
+/// parts of the c runtime, which the compiler process already has loaded.
+/// they are often linker scripts rather than libraries, e.g. `libm.so` with glibc, so they can't be loaded by name
+const C_RUNTIME_LIBS: [&str; 5] = ["c", "m", "pthread", "dl", "rt"];
+
+/// Loads the extra objects, the `-l` libraries and the `#link`s into `jit`, like [link_output] passes them to the linker.
+/// Libraries are searched in the `-L` directories first, shared before static, then by name in the system's library path,
+/// except for the [C_RUNTIME_LIBS], which are already there.
+fn load_native(jit: &mut Jit, args: &Args, links: &[String]) -> Result<(), ParseError> {
+    for object in &args.objects {
+        if [".a", ".lib"].iter().any(|ext| object.ends_with(ext)) {
+            jit.load_archive(object)?
+        } else if [".so", ".dylib", ".dll"].iter().any(|ext| object.ends_with(ext)) {
+            jit.load_library(object)?
+        } else {
+            jit.add_object(object)?
+        }
+    }
+    let mut libs = links.to_vec();
+    libs.extend(args.libs.iter().filter(|lib| !links.contains(lib)).cloned());
+    for lib in libs {
+        let shared = format!("{DLL_PREFIX}{lib}{DLL_SUFFIX}");
+        let candidates = args.lib_dirs.iter()
+            .flat_map(|dir| [format!("{dir}/{shared}"), format!("{dir}/lib{lib}.a")])
+            .collect::<Vec<_>>();
+        match candidates.iter().find(|path| Path::new(path).is_file()) {
+            Some(path) if path.ends_with(".a") => jit.load_archive(path)?,
+            Some(path) => jit.load_library(path)?,
+            None if C_RUNTIME_LIBS.contains(&lib.as_str()) => {}
+            None => jit.load_library(&shared).map_err(|e| e.when(&format!("looking for library {lib}, tried {}",
+                candidates.iter().chain([&shared]).cloned().collect::<Vec<_>>().join(", "))))?
+        }
+    }
+    Ok(())
+}
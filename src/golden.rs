@@ -0,0 +1,178 @@
+use std::{env, fs, panic, process, thread};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use crate::cli::Args;
+use crate::driver;
+use crate::source::{ParseError, ParseET};
+
+/// how long a test program may run before it is killed
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// subdirectory of the test directory with programs that have to fail to compile
+const COMPILE_FAIL_DIR: &str = "compile-fail";
+/// entry of a test made of several files, which is a subdirectory of the test directory containing this file
+const MULTI_FILE_ENTRY: &str = "main.mi";
+/// marks the line a compile-fail test is expected to report an error at, followed by the category of the error,
+/// e.g. `Parsing error:`, and part of the message
+const ERROR_ANNOTATION: &str = "//~ ERROR";
+
+/// compiles and runs every `.mi` file in `args.input`, both as an executable and in the jit of `run`,
+/// comparing stdout and exit code to the `.expected` file next to it.
+/// with `args.bless` the `.expected` files are written instead.
+/// every `.mi` file in its `compile-fail` subdirectory has to fail to compile as described by its `//~ ERROR` annotation.
+/// a subdirectory with a `main.mi` is one program, compiled from it and the other `.mi` files next to it
+pub(crate) fn test(args: &Args) -> Result<(), ParseError> {
+    let dir = Path::new(&args.input);
+    let mut tests = mi_files(dir)?.into_iter().map(|path| (vec![path], false)).collect::<Vec<_>>();
+    let mut subdirs = fs::read_dir(dir)?.map(|entry| entry.map(|e| e.path())).collect::<Result<Vec<_>, _>>()?;
+    subdirs.sort();
+    for sub in subdirs {
+        let main = sub.join(MULTI_FILE_ENTRY);
+        if main.is_file() {
+            tests.push(([main.clone()].into_iter().chain(mi_files(&sub)?.into_iter().filter(|path| *path != main)).collect(), false))
+        }
+    }
+    if dir.join(COMPILE_FAIL_DIR).is_dir() {
+        tests.extend(mi_files(&dir.join(COMPILE_FAIL_DIR))?.into_iter().map(|path| (vec![path], true)));
+    }
+    let out_dir = env::temp_dir().join(format!("minimal_language-test-{}", process::id()));
+    fs::create_dir_all(&out_dir)?;
+    let mut failed = vec![];
+    for (files, compile_fail) in &tests {
+        let path = &files[0];
+        let name = path.strip_prefix(dir).unwrap_or(path).with_extension("").to_string_lossy().into_owned();
+        let result = if *compile_fail {
+            compile_fail_test(path)
+        } else {
+            run_test(files, &out_dir.join(name.replace(['/', '\\'], "_")), args.bless)
+        };
+        match result {
+            Ok(()) if args.bless && !compile_fail => println!("test {name} ... blessed"),
+            Ok(()) => println!("test {name} ... ok"),
+            Err(e) => {
+                println!("test {name} ... FAILED");
+                failed.push((name, e))
+            }
+        }
+    }
+    fs::remove_dir_all(&out_dir)?;
+    for (name, e) in &failed {
+        println!("\n---- {name} ----\n{e}")
+    }
+    println!("\n{} passed, {} failed", tests.len() - failed.len(), failed.len());
+    if !failed.is_empty() {
+        return Err(ParseET::TestFailure(format!("{} of {} tests failed", failed.len(), tests.len())).error())
+    }
+    Ok(())
+}
+
+fn mi_files(dir: &Path) -> Result<Vec<PathBuf>, ParseError> {
+    let mut files = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<PathBuf>, _>>()?;
+    files.retain(|path| path.is_file() && path.extension().map_or(false, |ext| ext == "mi"));
+    files.sort();
+    Ok(files)
+}
+
+/// builds and runs the program compiled from `files`, the first of which has the `.expected` file next to it,
+/// then runs it again with `run`, which has to print the same
+fn run_test(files: &[PathBuf], exe: &Path, bless: bool) -> Result<(), String> {
+    let source = &files[0];
+    let files = files.iter().map(|path| path.to_string_lossy().into_owned()).collect::<Vec<_>>();
+    let args = Args::parse(["build".to_string()].into_iter()
+        .chain(files.iter().cloned())
+        .chain(["-o".to_string(), exe.to_string_lossy().into_owned(), "--no-cache".to_string()]))?;
+    driver::build(&args).map_err(|e| e.to_string())?;
+    let actual = run_with_timeout(Command::new(exe))?;
+    let expected_path = source.with_extension("expected");
+    if bless {
+        return fs::write(&expected_path, actual).map_err(|e| format!("could not write {}: {e}", expected_path.display()))
+    }
+    let expected = fs::read_to_string(&expected_path)
+        .map_err(|e| format!("could not read {}: {e}\nrun with --bless to create it", expected_path.display()))?;
+    if expected != actual {
+        return Err(format!("expected:\n{expected}\nfound:\n{actual}"))
+    }
+    // in another process, as the program's stdout can only be captured there
+    let compiler = env::current_exe().map_err(|e| format!("could not find the compiler: {e}"))?;
+    let mut jit = Command::new(compiler);
+    jit.arg("run").args(&files).arg("--no-cache");
+    let actual = run_with_timeout(jit)?;
+    if expected != actual {
+        return Err(format!("expected:\n{expected}\nfound with run:\n{actual}"))
+    }
+    Ok(())
+}
+
+/// compiles `source`, expecting an error of the category the `//~ ERROR` annotation starts with on the line of the annotation,
+/// whose message contains the rest of the annotation
+fn compile_fail_test(source: &Path) -> Result<(), String> {
+    let text = fs::read_to_string(source).map_err(|e| format!("could not read {}: {e}", source.display()))?;
+    let annotations = text.lines().enumerate()
+        .filter_map(|(i, line)| line.split_once(ERROR_ANNOTATION).map(|(_, message)| (i + 1, message.trim())))
+        .collect::<Vec<_>>();
+    // compilation stops at the first error, so there is only ever one to expect
+    let [(line, message)] = annotations[..] else {
+        return Err(format!("expected exactly one `{ERROR_ANNOTATION}` annotation, found {}", annotations.len()))
+    };
+    let args = Args::parse(["check", &source.to_string_lossy()].into_iter().map(String::from))?;
+    // panics are reported as failures, they should have been diagnostics
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let result = panic::catch_unwind(|| driver::check(&args));
+    panic::set_hook(hook);
+    let error = match result {
+        Ok(Ok(())) => return Err(format!("compiled successfully, expected an error at line {line}: {message}")),
+        Ok(Err(error)) => error,
+        Err(payload) => return Err(format!("compiler panicked: {}", payload.downcast_ref::<String>().cloned()
+            .or(payload.downcast_ref::<&str>().map(|s| s.to_string()))
+            .unwrap_or_default()))
+    };
+    let found = error.et().to_string();
+    // the first line names the kind of error, the indented lines below are the message
+    let (category, detail) = found.split_once('\n').unwrap_or((&found, ""));
+    let matches = message.strip_prefix(category).is_some_and(|expected| detail.replace("\n    ", " ").contains(expected.trim()));
+    if error.line() != Some(line) || !matches {
+        return Err(format!("expected an error at line {line}: {message}\nfound:\n{error}"))
+    }
+    Ok(())
+}
+
+/// runs `command`, returning its stdout followed by its exit code in the format of the `.expected` files
+fn run_with_timeout(mut command: Command) -> Result<String, String> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("could not run {}: {e}", command.get_program().to_string_lossy()))?;
+    // read on another thread so a full pipe can not block the program
+    let mut stdout = child.stdout.take().unwrap();
+    let reader = thread::spawn(move || {
+        let mut buffer = vec![];
+        stdout.read_to_end(&mut buffer).map(|_| buffer)
+    });
+    let start = Instant::now();
+    let status = loop {
+        match child.try_wait().map_err(|e| e.to_string())? {
+            Some(status) => break status,
+            None if start.elapsed() > TIMEOUT => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("timed out after {}s", TIMEOUT.as_secs()))
+            }
+            None => thread::sleep(Duration::from_millis(10))
+        }
+    };
+    let stdout = reader.join().unwrap().map_err(|e| e.to_string())?;
+    let mut output = String::from_utf8_lossy(&stdout).into_owned();
+    if !output.is_empty() && !output.ends_with('\n') {
+        output.push('\n')
+    }
+    match status.code() {
+        Some(code) => output.push_str(&format!("exit code: {code}\n")),
+        None => output.push_str("terminated by signal\n")
+    }
+    Ok(output)
+}
@@ -0,0 +1,137 @@
+use std::ffi::{c_char, c_int, c_void};
+use std::ptr;
+use llvm_sys::{bit_reader, bit_writer, core, error};
+use llvm_sys::error::LLVMErrorRef;
+use llvm_sys::orc2::{self, lljit};
+use llvm_sys::orc2::lljit::LLVMOrcLLJITRef;
+use llvm_sys::prelude::LLVMModuleRef;
+use crate::{c_str, c_str_ptr};
+use crate::source::{ParseError, ParseET};
+use crate::target::{init_native, llvm_message, take_error};
+
+extern "C" {
+    fn fflush(stream: *mut c_void) -> c_int;
+}
+
+/// Flushes the stdout buffer of libc, which jitted programs print through.
+/// It would otherwise only be flushed when the compiler exits, after everything the compiler prints itself.
+pub(crate) fn flush_c_stdout() {
+    unsafe { fflush(ptr::null_mut()) };
+}
+
+fn check(err: LLVMErrorRef) -> Result<(), ParseError> {
+    take_error(err).map_err(|e| ParseET::JitError(e).error())
+}
+
+/// An ORC LLJIT instance which resolves undefined symbols (like `puts`) against the loaded libraries and the host process.
+pub(crate) struct Jit {
+    jit: LLVMOrcLLJITRef,
+}
+
+impl Jit {
+    pub(crate) fn new() -> Result<Self, ParseError> {
+        init_native()?;
+        unsafe {
+            let mut jit = ptr::null_mut();
+            check(lljit::LLVMOrcCreateLLJIT(&mut jit, ptr::null_mut()))?;
+            let jit = Self { jit };
+            let mut generator = ptr::null_mut();
+            check(orc2::LLVMOrcCreateDynamicLibrarySearchGeneratorForProcess(
+                &mut generator, lljit::LLVMOrcLLJITGetGlobalPrefix(jit.jit), None, ptr::null_mut()))?;
+            orc2::LLVMOrcJITDylibAddGenerator(lljit::LLVMOrcLLJITGetMainJITDylib(jit.jit), generator);
+            Ok(jit)
+        }
+    }
+
+    /// Adds a copy of `module` to the jit. The module itself stays owned by the caller.
+    ///
+    /// The compiler builds everything in the global context, but the jit needs a module owned by
+    /// a thread safe context, so the module is moved over by round tripping it through bitcode.
+    pub(crate) fn add_module(&mut self, module: LLVMModuleRef) -> Result<(), ParseError> {
+        unsafe {
+            let buffer = bit_writer::LLVMWriteBitcodeToMemoryBuffer(module);
+            let ts_ctx = orc2::LLVMOrcCreateNewThreadSafeContext();
+            let mut copy = ptr::null_mut();
+            let failed = bit_reader::LLVMParseBitcodeInContext2(orc2::LLVMOrcThreadSafeContextGetContext(ts_ctx), buffer, &mut copy);
+            core::LLVMDisposeMemoryBuffer(buffer);
+            if failed != 0 {
+                orc2::LLVMOrcDisposeThreadSafeContext(ts_ctx);
+                return Err(ParseET::JitError(format!("could not copy module into the jit context")).error())
+            }
+            let ts_module = orc2::LLVMOrcCreateNewThreadSafeModule(copy, ts_ctx);
+            // the module keeps its context alive
+            orc2::LLVMOrcDisposeThreadSafeContext(ts_ctx);
+            check(lljit::LLVMOrcLLJITAddLLVMIRModule(self.jit, lljit::LLVMOrcLLJITGetMainJITDylib(self.jit), ts_module))
+        }
+    }
+
+    /// makes the symbols of the shared library at `path` available to the jitted code
+    pub(crate) fn load_library(&mut self, path: &str) -> Result<(), ParseError> {
+        unsafe {
+            let mut generator = ptr::null_mut();
+            check(orc2::LLVMOrcCreateDynamicLibrarySearchGeneratorForPath(
+                &mut generator, c_str_ptr!(path), lljit::LLVMOrcLLJITGetGlobalPrefix(self.jit), None, ptr::null_mut()))
+                .map_err(|e| e.when(&format!("loading {path}")))?;
+            orc2::LLVMOrcJITDylibAddGenerator(lljit::LLVMOrcLLJITGetMainJITDylib(self.jit), generator);
+        }
+        Ok(())
+    }
+
+    /// makes the static library at `path` available to the jitted code, its members are linked in when they are needed
+    pub(crate) fn load_archive(&mut self, path: &str) -> Result<(), ParseError> {
+        unsafe {
+            let mut generator = ptr::null_mut();
+            check(orc2::LLVMOrcCreateStaticLibrarySearchGeneratorForPath(&mut generator,
+                lljit::LLVMOrcLLJITGetObjLinkingLayer(self.jit), c_str_ptr!(path), lljit::LLVMOrcLLJITGetTripleString(self.jit)))
+                .map_err(|e| e.when(&format!("loading {path}")))?;
+            orc2::LLVMOrcJITDylibAddGenerator(lljit::LLVMOrcLLJITGetMainJITDylib(self.jit), generator);
+        }
+        Ok(())
+    }
+
+    /// links the object file at `path` into the jit
+    pub(crate) fn add_object(&mut self, path: &str) -> Result<(), ParseError> {
+        unsafe {
+            let mut buffer = ptr::null_mut();
+            let mut msg = ptr::null_mut();
+            if core::LLVMCreateMemoryBufferWithContentsOfFile(c_str_ptr!(path), &mut buffer, &mut msg) != 0 {
+                return Err(ParseET::JitError(llvm_message(msg)).error().when(&format!("loading {path}")))
+            }
+            // the jit takes ownership of the buffer
+            check(lljit::LLVMOrcLLJITAddObjectFile(self.jit, lljit::LLVMOrcLLJITGetMainJITDylib(self.jit), buffer))
+                .map_err(|e| e.when(&format!("loading {path}")))
+        }
+    }
+
+    /// address of the symbol `name`, compiling it if necessary
+    pub(crate) fn lookup(&self, name: &str) -> Result<u64, ParseError> {
+        let mut addr = 0;
+        check(unsafe { lljit::LLVMOrcLLJITLookup(self.jit, &mut addr, c_str_ptr!(name) as *const c_char) })
+            .map_err(|e| e.when(&format!("looking up symbol {name}")))?;
+        Ok(addr)
+    }
+
+    /// calls the function `name`, which has to take no arguments and return nothing
+    pub(crate) fn call(&self, name: &str) -> Result<(), ParseError> {
+        let addr = self.lookup(name)?;
+        let f: extern "C" fn() = unsafe { std::mem::transmute(addr as usize) };
+        f();
+        Ok(())
+    }
+
+    /// runs the synthesized `main` of the jit's modules with `args` as argv and returns its exit code
+    pub(crate) fn run_main(&self, args: &[String]) -> Result<i32, ParseError> {
+        let addr = self.lookup("main")?;
+        let main: extern "C" fn(i32, *const *const c_char) -> i32 = unsafe { std::mem::transmute(addr as usize) };
+        let args = args.iter().map(|arg| c_str!(arg)).collect::<Vec<_>>();
+        // argv is terminated by a null pointer, like the one the c runtime passes
+        let argv = args.iter().map(|arg| arg.as_ptr()).chain([ptr::null()]).collect::<Vec<_>>();
+        Ok(main(args.len() as i32, argv.as_ptr()))
+    }
+}
+
+impl Drop for Jit {
+    fn drop(&mut self) {
+        unsafe { error::LLVMConsumeError(lljit::LLVMOrcDisposeLLJIT(self.jit)) }
+    }
+}
@@ -0,0 +1,123 @@
+#![feature(pattern)]
+#![feature(try_blocks)]
+#![feature(stmt_expr_attributes)]
+
+//! The compiler for `.mi` files as a library.
+//!
+//! ```no_run
+//! use minimal_language::{compile_source, Options, OptLevel, Source, TargetSpec};
+//!
+//! let source = Source::from_file("testing/fibonacci.mi".to_string()).unwrap();
+//! let module = compile_source(source, "fibonacci", &Options::default()).unwrap();
+//! println!("{}", module.to_ir());
+//! let object = module.to_object(&TargetSpec::default(), OptLevel::O2).unwrap();
+//! ```
+
+use std::collections::HashMap;
+use llvm_sys::{bit_writer, core};
+use llvm_sys::prelude::LLVMModuleRef;
+use llvm_sys::target_machine::{self, LLVMCodeGenFileType};
+
+mod tokens;
+mod source;
+mod compiler;
+mod cli;
+mod driver;
+mod target;
+mod link;
+mod jit;
+mod repl;
+mod optimize;
+mod debug_info;
+mod manifest;
+mod golden;
+mod cache;
+mod timing;
+mod watch;
+
+pub use cli::run_cli;
+pub use optimize::OptLevel;
+pub use source::{CodePoint, ParseError, ParseET, Source, SourceType, Span};
+pub use target::TargetSpec;
+pub use tokens::tok_iter::TokIter;
+pub use tokens::tokenize::tokenize;
+pub use tokens::tokens::{glued, Literal, NumLit, NumLitTy, Token, TokenType};
+
+macro_rules! c_str {
+    ($s:literal) => (
+        #[allow(unused_unsafe)]
+        unsafe { std::ffi::CStr::from_ptr(concat!($s, "\0").as_ptr() as *const i8) }
+    );
+    ($s:expr) => (
+        std::ffi::CString::new($s.to_string()).expect("string contains a null byte")
+    );
+}
+
+macro_rules! c_str_ptr {
+    ($s:expr) => (
+        $crate::c_str!($s).as_ptr()
+    );
+}
+
+pub(crate) use c_str;
+pub(crate) use c_str_ptr;
+
+/// how to [compile]
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// attach dwarf debug info for the source to the module
+    pub debug_info: bool,
+    /// compile a library: no `main` wrapper is generated and every `fn` keeps its own name
+    pub library: bool,
+}
+
+/// An llvm module compiled from `.mi` source, disposed when dropped.
+pub struct Module(LLVMModuleRef);
+
+impl Module {
+    /// the module as textual llvm ir
+    pub fn to_ir(&self) -> String {
+        target::llvm_message(unsafe { core::LLVMPrintModuleToString(self.0) })
+    }
+
+    /// the module as llvm bitcode
+    pub fn to_bitcode(&self) -> Vec<u8> {
+        target::buffer_bytes(unsafe { bit_writer::LLVMWriteBitcodeToMemoryBuffer(self.0) })
+    }
+
+    /// An object file for `target`, optimized at `opt`.
+    /// A copy of the module is optimized, so this can be called again with other arguments.
+    pub fn to_object(&self, target: &TargetSpec, opt: OptLevel) -> Result<Vec<u8>, ParseError> {
+        let tm = target::target_machine(target, opt)?;
+        let module = unsafe { core::LLVMCloneModule(self.0) };
+        target::configure_module(module, tm);
+        let r = optimize::optimize(module, tm, opt)
+            .and_then(|_| target::emit_to_memory(tm, module, LLVMCodeGenFileType::LLVMObjectFile));
+        unsafe {
+            core::LLVMDisposeModule(module);
+            target_machine::LLVMDisposeTargetMachine(tm);
+        }
+        r
+    }
+
+    /// the underlying llvm module, which stays owned by `self`
+    pub fn as_raw(&self) -> LLVMModuleRef {
+        self.0
+    }
+}
+
+impl Drop for Module {
+    fn drop(&mut self) {
+        unsafe { core::LLVMDisposeModule(self.0) }
+    }
+}
+
+/// compiles `tokens` from [tokenize] into the module `name`
+pub fn compile(tokens: Vec<Token>, name: &str, options: &Options) -> Result<Module, ParseError> {
+    compiler::compile(TokIter::new(tokens), name, options.debug_info, options.library, &HashMap::new(), None, &compiler::IncludedFns::Define).map(Module)
+}
+
+/// tokenizes and compiles `source` into the module `name`
+pub fn compile_source(source: Source, name: &str, options: &Options) -> Result<Module, ParseError> {
+    compile(tokenize(source)?, name, options)
+}
@@ -0,0 +1,96 @@
+use std::{env, fs};
+use std::ffi::c_void;
+use std::path::Path;
+use std::env::consts::EXE_SUFFIX;
+use std::process::Command;
+use llvm_sys::{core, linker};
+use llvm_sys::prelude::{LLVMDiagnosticInfoRef, LLVMModuleRef};
+use crate::source::{ParseError, ParseET};
+use crate::target::llvm_message;
+
+/// linker drivers tried in order when no `--linker` is given. a bare `ld` is not one, it does not know the c runtime
+const LINKERS: [&str; 3] = ["cc", "clang", "gcc"];
+/// archivers tried in order for static libraries
+const ARCHIVERS: [&str; 2] = ["ar", "llvm-ar"];
+
+/// returns `explicit` if given, otherwise the first of [LINKERS] found in `PATH`
+pub(crate) fn find_linker(explicit: Option<&str>) -> Result<String, ParseError> {
+    if let Some(linker) = explicit {
+        return Ok(linker.to_string())
+    }
+    find_program(&LINKERS)
+        .ok_or(ParseET::LinkError(format!("no linker found in PATH, tried: {}", LINKERS.join(", "))).error())
+}
+
+fn find_program(programs: &[&str]) -> Option<String> {
+    let path = env::var_os("PATH").unwrap_or_default();
+    for program in programs {
+        for dir in env::split_paths(&path) {
+            let candidate = dir.join(format!("{program}{EXE_SUFFIX}"));
+            if candidate.is_file() {
+                return Some(candidate.to_string_lossy().into_owned())
+            }
+        }
+    }
+    None
+}
+
+/// links `objects` into `output`, passing each of `lib_dirs` as `-L<dir>` and each of `libs` as `-l<lib>`.
+/// `shared` produces a shared library instead of an executable
+pub(crate) fn link(linker: &str, objects: &[String], lib_dirs: &[String], libs: &[String], shared: bool, output: &str) -> Result<(), ParseError> {
+    let mut command = Command::new(linker);
+    if shared {
+        command.arg("-shared");
+    }
+    command.args(objects)
+        .args(lib_dirs.iter().map(|dir| format!("-L{dir}")))
+        .args(libs.iter().map(|lib| format!("-l{lib}")))
+        .args(["-o", output]);
+    run(command, linker)
+}
+
+/// bundles `objects` into the static library `output`
+pub(crate) fn archive(objects: &[String], output: &str) -> Result<(), ParseError> {
+    let archiver = find_program(&ARCHIVERS)
+        .ok_or(ParseET::LinkError(format!("no archiver found in PATH, tried: {}", ARCHIVERS.join(", "))).error())?;
+    // ar adds to existing archives instead of replacing them
+    if Path::new(output).exists() {
+        fs::remove_file(output)?;
+    }
+    let mut command = Command::new(&archiver);
+    command.arg("rcs").arg(output).args(objects);
+    run(command, &archiver)
+}
+
+fn run(mut command: Command, program: &str) -> Result<(), ParseError> {
+    let status = command.status()
+        .map_err(|e| ParseError::from(e).when(&format!("running {program}")))?;
+    if !status.success() {
+        return Err(ParseET::LinkError(format!("{program} exited with {status}")).error())
+    }
+    Ok(())
+}
+
+extern "C" fn collect_diagnostic(info: LLVMDiagnosticInfoRef, messages: *mut c_void) {
+    let messages = unsafe { &mut *(messages as *mut Vec<String>) };
+    messages.push(llvm_message(unsafe { core::LLVMGetDiagInfoDescription(info) }));
+}
+
+/// links `modules` into `dest`, consuming them. duplicate definitions are reported as [ParseET::LinkError]
+pub(crate) fn link_modules(dest: LLVMModuleRef, modules: Vec<LLVMModuleRef>) -> Result<(), ParseError> {
+    let mut messages: Vec<String> = vec![];
+    let mut modules = modules.into_iter();
+    unsafe {
+        let context = core::LLVMGetModuleContext(dest);
+        let (handler, handler_context) = (core::LLVMContextGetDiagnosticHandler(context), core::LLVMContextGetDiagnosticContext(context));
+        core::LLVMContextSetDiagnosticHandler(context, Some(collect_diagnostic), &mut messages as *mut Vec<String> as *mut c_void);
+        let failed = modules.by_ref().any(|module| linker::LLVMLinkModules2(dest, module) != 0);
+        core::LLVMContextSetDiagnosticHandler(context, handler, handler_context);
+        // whatever was not linked yet would leak otherwise
+        modules.for_each(|module| core::LLVMDisposeModule(module));
+        if failed {
+            return Err(ParseET::LinkError(messages.join("\n    ")).error().when("linking modules"))
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use crate::optimize::OptLevel;
+
+/// file name of the manifest the cli looks for in the working directory when no input file is given
+pub(crate) const MANIFEST_FILE: &str = "mi.toml";
+
+/// A project manifest, written in a small subset of toml:
+/// ```toml
+/// entry = "src/main.mi"
+/// sources = ["src/parser.mi"]
+/// output = "main"
+/// include = ["lib"]
+/// libs = ["m"]
+///
+/// [profile.release]
+/// opt-level = 3
+/// debug = false
+/// ```
+/// Paths are relative to the directory of the manifest.
+#[derive(Debug, Clone)]
+pub(crate) struct Manifest {
+    pub(crate) entry: String,
+    pub(crate) sources: Vec<String>,
+    pub(crate) output: Option<String>,
+    pub(crate) include: Vec<String>,
+    pub(crate) libs: Vec<String>,
+    pub(crate) profiles: HashMap<String, Profile>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Profile {
+    pub(crate) opt: OptLevel,
+    pub(crate) debug_info: bool,
+}
+
+impl Profile {
+    /// the built in `debug` and `release` profiles, which a manifest may override
+    pub(crate) fn defaults() -> HashMap<String, Profile> {
+        HashMap::from([
+            ("debug".to_string(), Profile { opt: OptLevel::O0, debug_info: true }),
+            ("release".to_string(), Profile { opt: OptLevel::O3, debug_info: false }),
+        ])
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    String(String),
+    Integer(i64),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+impl Manifest {
+    pub(crate) fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("could not read {path}: {e}"))?;
+        let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+        let relative = |p: String| if dir.as_os_str().is_empty() || Path::new(&p).is_absolute() {
+            p
+        } else {
+            dir.join(p).to_string_lossy().into_owned()
+        };
+        let mut entry = None;
+        let mut sources = vec![];
+        let mut output = None;
+        let mut include = vec![];
+        let mut libs = vec![];
+        let mut profiles = Profile::defaults();
+        let mut section = String::new();
+        for (i, line) in text.lines().enumerate() {
+            let err = |e: String| format!("{path}:{}: {e}", i + 1);
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = name.trim().to_string();
+                if let Some(profile) = section.strip_prefix("profile.") {
+                    profiles.entry(profile.to_string()).or_insert(Profile { opt: OptLevel::O0, debug_info: false });
+                } else {
+                    return Err(err(format!("unknown section [{section}]")))
+                }
+                continue
+            }
+            let (key, value) = line.split_once('=').ok_or_else(|| err(format!("expected `key = value`")))?;
+            let key = key.trim();
+            let value = parse_value(value.trim()).map_err(err)?;
+            match (section.strip_prefix("profile."), key, value) {
+                (None, "entry", Value::String(s)) => entry = Some(relative(s)),
+                (None, "output", Value::String(s)) => output = Some(relative(s)),
+                (None, "sources", Value::Array(a)) => sources = strings(a).map_err(err)?.into_iter().map(relative).collect(),
+                (None, "include", Value::Array(a)) => include = strings(a).map_err(err)?.into_iter().map(relative).collect(),
+                (None, "libs", Value::Array(a)) => libs = strings(a).map_err(err)?,
+                (Some(profile), "opt-level", v) => {
+                    let level = match v {
+                        Value::Integer(i) => i.to_string(),
+                        Value::String(s) => s,
+                        v => return Err(err(format!("expected optimization level, found {v:?}")))
+                    };
+                    profiles.get_mut(profile).unwrap().opt = OptLevel::parse(&level)
+                        .filter(|_| !level.is_empty())
+                        .ok_or_else(|| err(format!("unknown optimization level: {level}")))?;
+                }
+                (Some(profile), "debug", Value::Bool(b)) => profiles.get_mut(profile).unwrap().debug_info = b,
+                (_, key, v) => return Err(err(format!("unexpected key `{key}` with value {v:?}")))
+            }
+        }
+        Ok(Self {
+            entry: entry.ok_or(format!("{path}: missing `entry`"))?,
+            sources,
+            output,
+            include,
+            libs,
+            profiles,
+        })
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => ()
+        }
+    }
+    line
+}
+
+fn strings(values: Vec<Value>) -> Result<Vec<String>, String> {
+    values.into_iter().map(|v| match v {
+        Value::String(s) => Ok(s),
+        v => Err(format!("expected string, found {v:?}"))
+    }).collect()
+}
+
+fn parse_value(value: &str) -> Result<Value, String> {
+    match value {
+        "true" => Ok(Value::Bool(true)),
+        "false" => Ok(Value::Bool(false)),
+        v if v.starts_with('"') => v[1..].strip_suffix('"')
+            .filter(|s| !s.contains('"'))
+            .map(|s| Value::String(s.to_string()))
+            .ok_or(format!("invalid string: {v}")),
+        v if v.starts_with('[') => {
+            let inner = v[1..].strip_suffix(']').ok_or(format!("arrays have to be on a single line: {v}"))?;
+            inner.split(',')
+                .map(|e| e.trim())
+                .filter(|e| !e.is_empty())
+                .map(parse_value)
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::Array)
+        }
+        v => v.parse().map(Value::Integer).map_err(|_| format!("invalid value: {v}"))
+    }
+}
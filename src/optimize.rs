@@ -0,0 +1,59 @@
+use llvm_sys::prelude::LLVMModuleRef;
+use llvm_sys::target_machine::{LLVMCodeGenOptLevel, LLVMTargetMachineRef};
+use llvm_sys::transforms::pass_builder;
+use crate::c_str_ptr;
+use crate::source::{ParseError, ParseET};
+use crate::target::take_error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OptLevel {
+    #[default]
+    O0,
+    O1,
+    O2,
+    O3,
+    Os,
+    Oz,
+}
+
+impl OptLevel {
+    /// parses the part after `-O`, a bare `-O` means `-O2`
+    pub fn parse(level: &str) -> Option<Self> {
+        match level {
+            "0" => Some(OptLevel::O0),
+            "1" => Some(OptLevel::O1),
+            "2" | "" => Some(OptLevel::O2),
+            "3" => Some(OptLevel::O3),
+            "s" => Some(OptLevel::Os),
+            "z" => Some(OptLevel::Oz),
+            _ => None
+        }
+    }
+
+    pub(crate) fn codegen_level(&self) -> LLVMCodeGenOptLevel {
+        match self {
+            OptLevel::O0 => LLVMCodeGenOptLevel::LLVMCodeGenLevelNone,
+            OptLevel::O1 => LLVMCodeGenOptLevel::LLVMCodeGenLevelLess,
+            OptLevel::O2 | OptLevel::Os | OptLevel::Oz => LLVMCodeGenOptLevel::LLVMCodeGenLevelDefault,
+            OptLevel::O3 => LLVMCodeGenOptLevel::LLVMCodeGenLevelAggressive,
+        }
+    }
+}
+
+/// runs the default new pass manager pipeline of `level` on `module`, `-O0` leaves it untouched
+pub(crate) fn optimize(module: LLVMModuleRef, tm: LLVMTargetMachineRef, level: OptLevel) -> Result<(), ParseError> {
+    let pipeline = match level {
+        OptLevel::O0 => return Ok(()),
+        OptLevel::O1 => "default<O1>",
+        OptLevel::O2 => "default<O2>",
+        OptLevel::O3 => "default<O3>",
+        OptLevel::Os => "default<Os>",
+        OptLevel::Oz => "default<Oz>",
+    };
+    unsafe {
+        let options = pass_builder::LLVMCreatePassBuilderOptions();
+        let r = take_error(pass_builder::LLVMRunPasses(module, c_str_ptr!(pipeline), tm, options));
+        pass_builder::LLVMDisposePassBuilderOptions(options);
+        r.map_err(|e| ParseET::CodegenError(e).error().when(&format!("running {pipeline}")))
+    }
+}
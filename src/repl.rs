@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::io::{BufRead, ErrorKind, stdin, stdout, Write};
+use llvm_sys::core;
+use llvm_sys::prelude::{LLVMModuleRef, LLVMTypeRef, LLVMValueRef};
+use crate::c_str_ptr;
+use crate::compiler::{compile_repl, declare_all, ty_to_ty_str};
+use crate::jit::{self, Jit};
+use crate::source::{ParseError, ParseET, resolve, search_path, Source};
+use crate::tokens::tok_iter::TokIter;
+use crate::tokens::tokenize::tokenize;
+
+/// Reads declarations and statements from stdin and runs them right away.
+/// Input that ends in the middle of a statement is continued on the next line.
+/// `#include <path>` evaluates the declarations of a file, found relative to the working directory or in the include search path.
+pub(crate) fn repl() -> Result<(), ParseError> {
+    let mut jit = Jit::new()?;
+    // every input is its own module, they are kept alive so later inputs can declare their symbols
+    let mut modules: Vec<LLVMModuleRef> = vec![];
+    let mut varmap = HashMap::new();
+    let mut input = String::new();
+    let mut lines = stdin().lock().lines();
+    loop {
+        print!("{}", if input.is_empty() { ">> " } else { ".. " });
+        stdout().flush()?;
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => break
+        };
+        if input.is_empty() && line.trim() == ":quit" {
+            break
+        }
+        input.push_str(&line);
+        input.push('\n');
+
+        let id = modules.len();
+        let module = unsafe { core::LLVMModuleCreateWithName(c_str_ptr!(format!("repl_{id}"))) };
+        let mut input_varmap = declare_all(module, &varmap);
+        let r = eval(&mut jit, module, id, &input, &mut input_varmap);
+        match r {
+            Err(e) if matches!(e.et(), ParseET::EOF) => {
+                unsafe { core::LLVMDisposeModule(module) }
+                continue
+            }
+            Err(e) => {
+                unsafe { core::LLVMDisposeModule(module) }
+                eprintln!("{e}")
+            }
+            Ok(()) => {
+                modules.push(module);
+                varmap = input_varmap;
+            }
+        }
+        input.clear();
+    }
+    for module in modules {
+        unsafe { core::LLVMDisposeModule(module) }
+    }
+    Ok(())
+}
+
+fn eval(jit: &mut Jit, module: LLVMModuleRef, id: usize, input: &str,
+        varmap: &mut HashMap<String, (LLVMTypeRef, LLVMValueRef, bool)>) -> Result<(), ParseError> {
+    let source = match input.trim().strip_prefix("#include ") {
+        Some(path) => {
+            let file = resolve(".", path.trim(), &search_path(&[])).map_err(|tried| ParseET::IOError(std::io::Error::new(ErrorKind::NotFound,
+                format!("cannot find include {}, tried {}", path.trim(), tried.join(", ")))).error())?;
+            Source::from_file(file)?
+        }
+        None => Source::from_string(input.to_string())
+    };
+    let tokens = tokenize(source)?;
+    if tokens.is_empty() {
+        return Ok(())
+    }
+    let results = compile_repl(TokIter::new(tokens), module, id, varmap)?;
+    jit.add_module(module)?;
+    jit.call(&format!("__repl_{id}"))?;
+    jit::flush_c_stdout();
+    for (name, ty) in results {
+        let addr = jit.lookup(&name)? as usize;
+        let ty_str = ty_to_ty_str(ty);
+        let value = unsafe {
+            match ty_str.as_str() {
+                "bool" => format!("{}", *(addr as *const u8) != 0),
+                "i8" => format!("{}", *(addr as *const i8)),
+                "i32" => format!("{}", *(addr as *const i32)),
+                "i64" => format!("{}", *(addr as *const i64)),
+                "i128" => format!("{}", *(addr as *const i128)),
+                "ptr" => format!("{:?}", *(addr as *const *const u8)),
+                _ => format!("<unprintable>")
+            }
+        };
+        println!("{ty_str} {value}");
+    }
+    stdout().flush()?;
+    Ok(())
+}
@@ -1,15 +1,47 @@
+use std::env;
 use std::fmt::{Debug, Display, Formatter};
-use std::fs::File;
-use std::io::{Read};
+use std::fs::{self, File};
+use std::io::{ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::ptr;
 use std::rc::Rc;
 use crate::tokens::tokens::{Literal, NumLit};
 
 
 /* !! no clone !! */
 #[derive(PartialEq)]
-pub(crate) struct Source {
+pub struct Source {
     st: SourceType,
-    source: String
+    source: String,
+    links: Vec<String>,
+    /// the file this was read from, followed by every file it includes
+    files: Vec<SourceFile>,
+    imports: Vec<String>,
+    /// the index of the file in `files` and the line (counting from 0) of the `import` of each of `imports`
+    import_lines: Vec<(usize, usize)>,
+    /// where the text starting at an index came from: the index in `source`, the index of the file in `files`
+    /// and the index in its text. empty if the text is not from files
+    map: Vec<(usize, usize, usize)>,
+    /// the index in `source` every line starts at
+    line_starts: Vec<usize>,
+}
+
+/// A file that is part of a [Source], with its text before the includes were expanded.
+#[derive(Debug, Clone, PartialEq)]
+struct SourceFile {
+    st: SourceType,
+    text: String,
+    /// the index of the including file in [Source::files] and the line of the `#include`
+    included_from: Option<(usize, usize)>,
+    /// the index in `text` every line starts at
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    fn new(st: SourceType, text: String, included_from: Option<(usize, usize)>) -> Self {
+        let line_starts = line_starts(&text);
+        Self { st, text, included_from, line_starts }
+    }
 }
 
 impl Debug for Source {
@@ -19,51 +51,263 @@ impl Debug for Source {
 }
 
 impl Source {
-    pub(crate) fn from_file(path: String) -> Result<Self, ParseError> {
-        Ok(Self {
-            st: SourceType::File(path.clone()),
-            source: {
-                let mut f = File::open(path.clone())?;
-                let mut buffer = String::new();
-                f.read_to_string(&mut buffer)?;
-                fn include(path: String, mut file: String) -> String{
-                    let dir = if let Some((p, n)) = path.rsplit_once("/") {
-                        p
-                    } else {
-                        "."
-                    };
-                    let mut includes = vec![];
-                    for line in file.lines() {
-                        if line.starts_with("#include ") {
-                            includes.push(line.split_at(9).1.to_string())
-                        }
-                    }
-                    for incl in includes {
-                        let (path, name) = if let Some((p, n)) = incl.rsplit_once("/") {
-                            (p.to_string(), n.to_string())
-                        } else {
-                            (".".to_string(), incl.clone())
-                        };
-                        let include_file = format!("{}/{}/{}.mi", dir, path, name);
-                        let mut f = File::open(include_file.clone()).expect(&format!("error including file: {include_file}"));
-                        let mut buffer = String::new();
-                        f.read_to_string(&mut buffer).unwrap();
-                        buffer = include(include_file, buffer);
-                        file = file.replace(&format!("#include {}", incl), &buffer)
-                    }
-                    file
-                }
-                include(path, buffer)
-            }
+    /// reads the file at `path`, with `#include`s expanded and searched for like the cli does without `-I` or a manifest
+    pub fn from_file(path: String) -> Result<Self, ParseError> {
+        Self::from_file_with_includes(path, &[])
+    }
+
+    /// like [Source::from_file], but `#include`s not found next to the including file are searched in `include_dirs`
+    /// before `MI_PATH` and the standard library
+    pub fn from_file_with_includes(path: String, include_dirs: &[String]) -> Result<Self, ParseError> {
+        let mut f = File::open(path.clone())?;
+        let mut buffer = String::new();
+        f.read_to_string(&mut buffer)?;
+        Self::with_includes(SourceType::File(path), buffer, include_dirs)
+    }
+
+    /// reads the program from stdin, `#include`s are relative to the working directory
+    pub fn from_stdin(include_dirs: &[String]) -> Result<Self, ParseError> {
+        let mut buffer = String::new();
+        std::io::stdin().read_to_string(&mut buffer)?;
+        Self::with_includes(SourceType::Stdin, buffer, include_dirs)
+    }
+
+    /// like [Source::from_string], but with `#include`s expanded relative to the working directory
+    pub fn from_string_with_includes(source: String, include_dirs: &[String]) -> Result<Self, ParseError> {
+        Self::with_includes(SourceType::String, source, include_dirs)
+    }
+
+    fn with_includes(st: SourceType, source: String, include_dirs: &[String]) -> Result<Self, ParseError> {
+        let mut this = Self::from_string(String::new());
+        this.st = st.clone();
+        this.files.push(SourceFile::new(st.clone(), source, None));
+        include(0, &search_path(include_dirs), &mut this, &mut vec![st.to_string()])?;
+        this.line_starts = line_starts(&this.source);
+        Ok(this)
+    }
+
+    /// the text of the file with all includes expanded
+    pub fn text(&self) -> &str {
+        &self.source
+    }
+
+    /// libraries requested with `#link <lib>` in this file or its includes
+    pub fn links(&self) -> &[String] {
+        &self.links
+    }
+
+    /// the file this was read from, if any, and every file it includes
+    pub fn files(&self) -> impl Iterator<Item=&str> {
+        self.files.iter().filter_map(|file| match &file.st {
+            SourceType::File(path) => Some(path.as_str()),
+            _ => None
         })
     }
 
-    pub(crate) fn from_string(source: String) -> Self{
+    /// the files `import`ed by this file or its includes, each compiled as its own module
+    pub fn imports(&self) -> &[String] {
+        &self.imports
+    }
+
+    /// Reads the module `self.imports()[import]` like [Source::from_file_with_includes].
+    /// Errors reading it are reported at its `import` line.
+    pub(crate) fn load_import(&self, import: usize, include_dirs: &[String]) -> Result<Self, ParseError> {
+        let path = &self.imports[import];
+        let text = read(path).map_err(|et| self.import_error(import, et))?;
+        Self::with_includes(SourceType::File(path.clone()), text, include_dirs)
+    }
+
+    /// the error `et` at the line importing `self.imports()[import]`
+    pub(crate) fn import_error(&self, import: usize, et: ParseET) -> ParseError {
+        let (file, line) = self.import_lines[import];
+        line_error(et, self, file, line)
+    }
+
+    pub fn source_type(&self) -> &SourceType {
+        &self.st
+    }
+
+    pub fn from_string(source: String) -> Self{
         Self {
             st: SourceType::String,
-            source
+            line_starts: line_starts(&source),
+            source,
+            links: vec![],
+            files: vec![],
+            imports: vec![],
+            import_lines: vec![],
+            map: vec![],
         }
     }
+
+    /// the text, line starts and type of the file the byte `index` of the expanded text came from, and the index in that text
+    fn locate(&self, index: usize) -> (&str, &[usize], &SourceType, usize) {
+        match self.map.partition_point(|(start, _, _)| *start <= index).checked_sub(1) {
+            Some(i) => {
+                let (start, file, file_start) = self.map[i];
+                let file = &self.files[file];
+                (&file.text, &file.line_starts, &file.st, usize::min(file_start + index - start, file.text.len()))
+            }
+            None => (&self.source, &self.line_starts, &self.st, index)
+        }
+    }
+
+    /// where the file the byte `index` came from was included, innermost first, as `<file>:<line>`
+    fn included_from(&self, index: usize) -> Vec<String> {
+        let mut included_from = vec![];
+        let i = self.map.partition_point(|(start, _, _)| *start <= index).checked_sub(1);
+        let mut next = i.and_then(|i| self.files[self.map[i].1].included_from);
+        while let Some((file, line)) = next {
+            included_from.push(format!("{}:{line}", self.files[file].st));
+            next = self.files[file].included_from;
+        }
+        included_from
+    }
+}
+
+/// Appends the text of the file `file` of `collected` to its text, with the `#include`s expanded, and maps every line to where it came from.
+/// The `#link`s, `import`s and included files are collected too.
+/// Every file is expanded only once, `chain` are the files currently being expanded, to report include cycles.
+fn include(file: usize, include_dirs: &[String], collected: &mut Source, chain: &mut Vec<String>) -> Result<(), ParseError> {
+    let st = collected.files[file].st.clone();
+    let text = collected.files[file].text.clone();
+    // without a file, includes are looked up in the working directory
+    let dir = match &st {
+        SourceType::File(path) => path.rsplit_once("/").map(|(dir, _)| dir).unwrap_or("."),
+        _ => "."
+    };
+    let mut offset = 0;
+    for (i, line) in text.split_inclusive('\n').enumerate() {
+        let start = offset;
+        offset += line.len();
+        let content = line.trim_end_matches(['\r', '\n']);
+        let ending = &line[content.len()..];
+        // like everywhere else, a trailing comment is not part of the directive
+        let directive = content.split_once("//").map_or(content, |(d, _)| d).trim_end();
+        if let Some(incl) = directive.strip_prefix("#include ") {
+            let include_file = resolve(dir, incl, include_dirs).map_err(|tried| line_error(ParseET::IOError(std::io::Error::new(ErrorKind::NotFound,
+                format!("cannot find include {incl}, tried {}", tried.join(", ")))), collected, file, i))?;
+            if chain.iter().any(|f| same_file(f, &include_file)) {
+                let cycle = chain.iter().chain([&include_file]).cloned().collect::<Vec<_>>().join(" -> ");
+                return Err(line_error(ParseET::IncludeError(format!("include cycle: {cycle}")), collected, file, i))
+            }
+            // a file already expanded earlier is skipped, its declarations must not be there twice
+            if !collected.files().any(|f| same_file(f, &include_file)) {
+                let buffer = read(&include_file).map_err(|et| line_error(et, collected, file, i))?;
+                collected.files.push(SourceFile::new(SourceType::File(include_file.clone()), buffer, Some((file, i + 1))));
+                chain.push(include_file);
+                include(collected.files.len() - 1, include_dirs, collected, chain)?;
+                chain.pop();
+            }
+            map_line(collected, file, start + content.len(), ending);
+            continue
+        }
+        if let Some(lib) = directive.strip_prefix("#link ") {
+            if !collected.links.iter().any(|l| l == lib.trim()) {
+                collected.links.push(lib.trim().to_string())
+            }
+            // the directive lines are emptied, not removed, to keep the line numbers intact
+            map_line(collected, file, start, ending);
+            continue
+        }
+        if let Some(import) = content.strip_prefix("import ") {
+            // the line itself stays, the compiler reads the names to import from it
+            let path = import.split_whitespace().next().unwrap_or("");
+            let namespace = path.rsplit('/').next().unwrap();
+            if namespace.is_empty() || namespace.starts_with(|c: char| c.is_ascii_digit())
+                || !namespace.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(line_error(ParseET::ParseError("module path ending in a name".to_string(), format!("'{path}'")), collected, file, i))
+            }
+            let module = resolve(dir, path, include_dirs).map_err(|tried| line_error(ParseET::IOError(std::io::Error::new(ErrorKind::NotFound,
+                format!("cannot find module {path}, tried {}", tried.join(", ")))), collected, file, i))?;
+            if !collected.imports.contains(&module) {
+                collected.imports.push(module);
+                collected.import_lines.push((file, i));
+            }
+        }
+        map_line(collected, file, start, line);
+    }
+    Ok(())
+}
+
+/// appends `text`, which starts at `start` in the file `file`, to the text of `collected`
+fn map_line(collected: &mut Source, file: usize, start: usize, text: &str) {
+    // lines continuing the previous one in the same file need no entry of their own
+    let continues = collected.map.last().is_some_and(|&(s, f, fs)| f == file && fs + collected.source.len() - s == start);
+    if !continues {
+        collected.map.push((collected.source.len(), file, start));
+    }
+    collected.source.push_str(text);
+}
+
+/// `path` with symlinks and `.`/`..` resolved, so the same file is always recognized as the same
+pub(crate) fn canonical(path: &str) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path))
+}
+
+/// whether `a` and `b` are the same file, however the paths are written
+fn same_file(a: &str, b: &str) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b
+    }
+}
+
+/// environment variable with further include directories, separated like `PATH`
+pub(crate) const MI_PATH: &str = "MI_PATH";
+
+/// The directories `#include`s and `import`s are searched in after the directory of the including file:
+/// `dirs` (from `-I` and the manifest), then those in [MI_PATH], then the standard library next to the compiler.
+pub(crate) fn search_path(dirs: &[String]) -> Vec<String> {
+    let mut search_path = dirs.to_vec();
+    if let Some(path) = env::var_os(MI_PATH) {
+        search_path.extend(env::split_paths(&path).filter(|d| !d.as_os_str().is_empty()).map(|d| d.to_string_lossy().into_owned()));
+    }
+    search_path.extend(stdlib_dir());
+    search_path
+}
+
+/// Where the standard library is installed, `MI_STDLIB_DIR` at build time
+/// or otherwise `lib/minimal_language` in the prefix the compiler is installed to, e.g. `/usr/local`.
+fn stdlib_dir() -> Option<String> {
+    if let Some(dir) = option_env!("MI_STDLIB_DIR") {
+        return Some(dir.to_string())
+    }
+    let exe = env::current_exe().ok()?;
+    let prefix = exe.parent()?.parent()?;
+    Some(prefix.join("lib").join("minimal_language").to_string_lossy().into_owned())
+}
+
+/// The file `path` (without `.mi`) refers to, relative to `dir` or one of `include_dirs`.
+/// Returns every path tried if none exists.
+pub(crate) fn resolve(dir: &str, path: &str, include_dirs: &[String]) -> Result<String, Vec<String>> {
+    let mut candidates = Vec::<String>::new();
+    for d in [dir].into_iter().chain(include_dirs.iter().map(|d| d.as_str())) {
+        // `./` is left out, so paths stay readable in diagnostics
+        let candidate = if d == "." { format!("{path}.mi") } else { format!("{}/{path}.mi", d.trim_end_matches('/')) };
+        if !candidates.contains(&candidate) {
+            candidates.push(candidate)
+        }
+    }
+    candidates.iter().find(|c| Path::new(c).is_file()).cloned().ok_or(candidates)
+}
+
+/// the text of the file at `path`, failing e.g. for a directory, a file without read permission or one that is not utf-8
+fn read(path: &str) -> Result<String, ParseET> {
+    fs::read_to_string(path).map_err(|e| ParseET::IOError(std::io::Error::new(e.kind(), format!("cannot read {path}: {e}"))))
+}
+
+/// an error spanning the line `line` (counting from 0) of the file `file` of `collected`, for directives handled before tokenizing
+fn line_error(et: ParseET, collected: &Source, file: usize, line: usize) -> ParseError {
+    let text = &collected.files[file].text;
+    let start = text.split_inclusive('\n').take(line).map(str::len).sum::<usize>();
+    let len = text[start..].find('\n').unwrap_or(text.len() - start);
+    // just the text of the file, which still knows where it was included from
+    let mut source = Source::from_string(text.clone());
+    source.st = collected.st.clone();
+    source.files = collected.files.clone();
+    source.map = vec![(0, file, 0)];
+    et.at(Span { source: Rc::new(source), start, end: start + len.saturating_sub(1) })
 }
 
 pub(crate) struct SourceIter {
@@ -118,8 +362,9 @@ impl SourceIter {
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub(crate) enum SourceType {
+pub enum SourceType {
     File(String),
+    Stdin,
     String,
 }
 
@@ -127,13 +372,14 @@ impl Display for SourceType {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", match self {
             SourceType::File(f) =>  format!("{}", f),
+            SourceType::Stdin => format!("<stdin>"),
             SourceType::String => format!("<string>")
         })
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub(crate) struct CodePoint(Rc<Source>, usize);
+pub struct CodePoint(Rc<Source>, usize);
 
 #[allow(non_camel_case_types)]
 type line = usize;
@@ -145,15 +391,39 @@ impl CodePoint {
         Span::single(self)
     }
 
-    pub(crate) fn pos(&self) -> (line, index_in_line){
-        let first_part = &self.0.source[0..self.1];
-        let mut lines_split = first_part.split("\n").collect::<Vec<&str>>();
-        (lines_split.len(), lines_split.pop().unwrap().len())
+    /// line and column in the file the point is in, both starting at 1 and 0 respectively
+    pub fn pos(&self) -> (line, index_in_line){
+        let (_, line_starts, _, index) = self.0.locate(self.1);
+        line_col(line_starts, index)
+    }
+
+    /// the file the point is in, which is the included file for text that came from an `#include`
+    pub fn file(&self) -> &SourceType {
+        self.0.locate(self.1).2
+    }
+
+    /// the canonical path of the `#include`d file the point came from, `None` for the file itself
+    pub(crate) fn included_file(&self) -> Option<PathBuf> {
+        match self.file() {
+            SourceType::File(path) if self.file() != self.0.source_type() => Some(canonical(path)),
+            _ => None
+        }
     }
 }
 
+/// the index every line of `text` starts at
+fn line_starts(text: &str) -> Vec<usize> {
+    std::iter::once(0).chain(text.match_indices('\n').map(|(i, _)| i + 1)).collect()
+}
+
+/// line and column of `index` in the text with the `line_starts`, both starting at 1 and 0 respectively
+fn line_col(line_starts: &[usize], index: usize) -> (line, index_in_line) {
+    let line = line_starts.partition_point(|start| *start <= index);
+    (line, index - line_starts[line - 1])
+}
+
 #[derive(Clone, PartialEq)]
-pub(crate) struct Span {
+pub struct Span {
     pub(crate) source: Rc<Source>,
     pub(crate) start: usize,
     pub(crate) end: usize
@@ -182,11 +452,16 @@ impl Span {
          CodePoint(self.source.clone(), self.end))
     }
 
-    pub(crate) fn start(&self) -> CodePoint {
+    /// the source the span is in
+    pub fn source(&self) -> &Source {
+        &self.source
+    }
+
+    pub fn start(&self) -> CodePoint {
         CodePoint(self.source.clone(), self.start)
     }
 
-    pub(crate) fn end(&self) -> CodePoint {
+    pub fn end(&self) -> CodePoint {
         CodePoint(self.source.clone(), self.end)
     }
 
@@ -196,10 +471,15 @@ impl Span {
         self.end = usize::max(self.end, p.1);
     }
 
+    /// the lines of the span, and `line_pad` lines around it, from the file the span starts in
     pub(crate) fn render_span_code(&self, line_pad: usize) -> String {
-        let (sl, sp) = self.start().pos();
-        let (el, ep) = self.end().pos();
-        let lines_split = &self.source.source.split("\n").collect::<Vec<&str>>();
+        let (text, line_starts, st, start) = self.source.locate(self.start);
+        let (_, _, end_st, end) = self.source.locate(self.end);
+        // a span continuing in another file ends with the line it starts in here
+        let end = if ptr::eq(st, end_st) { end } else { start + text[start..].find('\n').unwrap_or(text.len() - start).saturating_sub(1) };
+        let (sl, sp) = line_col(line_starts, start);
+        let (el, ep) = line_col(line_starts, end);
+        let lines_split = &text.split("\n").collect::<Vec<&str>>();
         let mut render = vec![];
         for i in usize::max(sl.saturating_sub(line_pad), 1)..=usize::min(el+line_pad, lines_split.len()) {
             render.push(format!("{i:3} | {}", lines_split[i-1]));
@@ -227,7 +507,7 @@ impl Debug for Span {
 }
 
 #[derive(Debug)]
-pub(crate) struct ParseError {
+pub struct ParseError {
     et: ParseET,
     loc: Option<Span>,
     context: Vec<String>
@@ -242,6 +522,22 @@ impl ParseError {
         self.loc = Some(loc);
         self
     }
+    /// what kind of error this is
+    pub fn et(&self) -> &ParseET {
+        &self.et
+    }
+    /// where the error is, if it is known
+    pub fn span(&self) -> Option<&Span> {
+        self.loc.as_ref()
+    }
+    /// line the error starts at, if it has a location
+    pub fn line(&self) -> Option<usize> {
+        self.loc.as_ref().map(|loc| loc.start().pos().0)
+    }
+    /// what was being done when the error happened, innermost first
+    pub fn context(&self) -> &[String] {
+        &self.context
+    }
 }
 
 impl From<std::io::Error> for ParseError {
@@ -251,7 +547,7 @@ impl From<std::io::Error> for ParseError {
 }
 
 #[derive(Debug)]
-pub(crate) enum ParseET {
+pub enum ParseET {
     EOF,
     EmptyInput,
     IOError(std::io::Error),
@@ -259,6 +555,13 @@ pub(crate) enum ParseET {
     ParseError(String, String),
     ParseLiteralError(Literal, String),
     VariableError(String),
+    CodegenError(String),
+    LinkError(String),
+    IncludeError(String),
+    ImportError(String),
+    JitError(String),
+    VerificationError(String),
+    TestFailure(String),
 }
 
 impl ParseET {
@@ -278,39 +581,53 @@ impl ParseET {
     }
 }
 
+impl Display for ParseET {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", match self {
+            ParseET::EOF => format!("Input error:\n    reached end of file"),
+            ParseET::EmptyInput => format!("Input error:\n    input was empty"),
+            ParseET::IOError(e) => format!("IO error:\n    {}", e),
+            ParseET::TokenizationError(e) => format!("Tokenization error:\n    {}", e),
+            ParseET::ParseError(expected, found) => format!("Parsing error:\n    expected {expected} found {found}"),
+            ParseET::ParseLiteralError(lit, e) => format!("{} literal parsing error:\n    {}", match lit {
+                Literal::String(_) => "String",
+                Literal::Char(_) => "Char",
+                Literal::Number(NumLit::Integer(_), _) => "Integer",
+                Literal::Number(NumLit::Float(_), _) => "Float",
+                Literal::Bool(_) => "Bool",
+            }, e),
+            ParseET::VariableError(e) => format!("cant find variable:\n    {e}"),
+            ParseET::CodegenError(e) => format!("Codegen error:\n    {e}"),
+            ParseET::LinkError(e) => format!("Link error:\n    {e}"),
+            ParseET::IncludeError(e) => format!("Include error:\n    {e}"),
+            ParseET::ImportError(e) => format!("Import error:\n    {e}"),
+            ParseET::JitError(e) => format!("JIT error:\n    {e}"),
+            ParseET::VerificationError(e) => format!("Invalid IR:\n    {e}"),
+            ParseET::TestFailure(e) => format!("Test failure:\n    {e}"),
+        })
+    }
+}
+
 impl Display for ParseError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}{}{}",
-               match &self.et {
-                   ParseET::EOF => format!("Input error:\n    reached end of file"),
-                   ParseET::EmptyInput => format!("Input error:\n    input was empty"),
-                   ParseET::IOError(e) => format!("IO error:\n    {}", e),
-                   ParseET::TokenizationError(e) => format!("Tokenization error:\n    {}", e),
-                   ParseET::ParseError(expected, found) => format!("Parsing error:\n    expected {expected} found {found}"),
-                   ParseET::ParseLiteralError(lit, e) => format!("{} literal parsing error:\n    {}", match lit {
-                       Literal::String(_) => "String",
-                       Literal::Char(_) => "Char",
-                       Literal::Number(NumLit::Integer(_), _) => "Integer",
-                       Literal::Number(NumLit::Float(_), _) => "Float",
-                       Literal::Bool(_) => "Float",
-                   }, e),
-                   ParseET::VariableError(e) => format!("cant find variable:\n    {e}"),
-               },
+               self.et,
                if self.context.len() > 0 {
                    format!("\n    while {}", self.context.join("\n    while "))
                } else {
                    String::new()
                },
                if let Some(loc) = &self.loc {
-                   format!("{}\n{}",
+                   format!("{}{}\n{}",
                        if loc.start == loc.end {
                            let (l, p) = loc.start().pos();
-                           format!("\n\nat: {}: {}:{}", loc.source.st, l, p)
+                           format!("\n\nat: {}: {}:{}", loc.start().file(), l, p)
                        } else {
                            let (sl, sp) = loc.start().pos();
                            let (el, ep) = loc.end().pos();
-                           format!("\n\nat: {}: {}:{}..{}:{}", loc.source.st, sl, sp, el, ep)
+                           format!("\n\nat: {}: {}:{}..{}:{}", loc.start().file(), sl, sp, el, ep)
                        },
+                       loc.source.included_from(loc.start).iter().map(|at| format!("\n    included from {at}")).collect::<String>(),
                        loc.render_span_code(2)
                    )
                } else {
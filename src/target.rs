@@ -0,0 +1,150 @@
+use std::ffi::{c_char, CStr};
+use std::ptr;
+use std::sync::Once;
+use llvm_sys::{core, error, target, target_machine};
+use llvm_sys::error::LLVMErrorRef;
+use llvm_sys::prelude::{LLVMMemoryBufferRef, LLVMModuleRef};
+use llvm_sys::target_machine::{LLVMCodeGenFileType, LLVMCodeModel, LLVMRelocMode, LLVMTargetMachineRef};
+use crate::c_str_ptr;
+use crate::optimize::OptLevel;
+use crate::source::{ParseError, ParseET};
+
+/// copies a message allocated by llvm into a `String` and frees the original
+pub(crate) fn llvm_message(msg: *mut c_char) -> String {
+    if msg.is_null() {
+        return String::new()
+    }
+    unsafe {
+        let s = CStr::from_ptr(msg).to_string_lossy().into_owned();
+        core::LLVMDisposeMessage(msg);
+        s
+    }
+}
+
+/// turns an `LLVMErrorRef` into its message, consuming it
+pub(crate) fn take_error(err: LLVMErrorRef) -> Result<(), String> {
+    if err.is_null() {
+        return Ok(())
+    }
+    unsafe {
+        let msg = error::LLVMGetErrorMessage(err);
+        let s = CStr::from_ptr(msg).to_string_lossy().into_owned();
+        error::LLVMDisposeErrorMessage(msg);
+        Err(s)
+    }
+}
+
+pub(crate) fn init_native() -> Result<(), ParseError> {
+    static INIT: Once = Once::new();
+    let mut failed = false;
+    INIT.call_once(|| unsafe {
+        failed = target::LLVM_InitializeNativeTarget() != 0 || target::LLVM_InitializeNativeAsmPrinter() != 0;
+    });
+    if failed {
+        return Err(ParseET::CodegenError(format!("could not initialize the native target")).error())
+    }
+    Ok(())
+}
+
+fn init_all() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| unsafe {
+        target::LLVM_InitializeAllTargetInfos();
+        target::LLVM_InitializeAllTargets();
+        target::LLVM_InitializeAllTargetMCs();
+        target::LLVM_InitializeAllAsmPrinters();
+        target::LLVM_InitializeAllAsmParsers();
+    });
+}
+
+/// What to generate code for. Everything left out defaults to the host,
+/// except that a foreign triple defaults to llvm's default cpu for it without extra features.
+#[derive(Debug, Clone, Default)]
+pub struct TargetSpec {
+    pub triple: Option<String>,
+    pub cpu: Option<String>,
+    pub features: Option<String>,
+}
+
+/// creates a target machine for the host, producing position independent code
+pub(crate) fn native_target_machine(opt: OptLevel) -> Result<LLVMTargetMachineRef, ParseError> {
+    target_machine(&TargetSpec::default(), opt)
+}
+
+/// creates a target machine for `spec`, producing position independent code
+pub(crate) fn target_machine(spec: &TargetSpec, opt: OptLevel) -> Result<LLVMTargetMachineRef, ParseError> {
+    unsafe {
+        let (triple, cpu, features) = match &spec.triple {
+            None => {
+                init_native()?;
+                (llvm_message(target_machine::LLVMGetDefaultTargetTriple()),
+                 llvm_message(target_machine::LLVMGetHostCPUName()),
+                 llvm_message(target_machine::LLVMGetHostCPUFeatures()))
+            }
+            Some(triple) => {
+                init_all();
+                (llvm_message(target_machine::LLVMNormalizeTargetTriple(c_str_ptr!(triple))),
+                 String::new(),
+                 String::new())
+            }
+        };
+        let cpu = match spec.cpu.as_deref() {
+            Some("native") => llvm_message(target_machine::LLVMGetHostCPUName()),
+            Some(cpu) => cpu.to_string(),
+            None => cpu
+        };
+        let features = spec.features.clone().unwrap_or(features);
+        let mut tgt = ptr::null_mut();
+        let mut err = ptr::null_mut();
+        if target_machine::LLVMGetTargetFromTriple(c_str_ptr!(triple), &mut tgt, &mut err) != 0 {
+            return Err(ParseET::CodegenError(format!("unsupported target '{triple}': {}", llvm_message(err))).error())
+        }
+        Ok(target_machine::LLVMCreateTargetMachine(tgt, c_str_ptr!(triple), c_str_ptr!(cpu), c_str_ptr!(features),
+                                                   opt.codegen_level(),
+                                                   LLVMRelocMode::LLVMRelocPIC,
+                                                   LLVMCodeModel::LLVMCodeModelDefault))
+    }
+}
+
+/// sets the triple and data layout of `module` to the ones of `tm`
+pub(crate) fn configure_module(module: LLVMModuleRef, tm: LLVMTargetMachineRef) {
+    unsafe {
+        let triple = target_machine::LLVMGetTargetMachineTriple(tm);
+        core::LLVMSetTarget(module, triple);
+        llvm_message(triple);
+        let layout = target_machine::LLVMCreateTargetDataLayout(tm);
+        target::LLVMSetModuleDataLayout(module, layout);
+        target::LLVMDisposeTargetData(layout);
+    }
+}
+
+pub(crate) fn emit_to_file(tm: LLVMTargetMachineRef, module: LLVMModuleRef, path: &str, ft: LLVMCodeGenFileType) -> Result<(), ParseError> {
+    let mut err = ptr::null_mut();
+    let failed = unsafe {
+        target_machine::LLVMTargetMachineEmitToFile(tm, module, c_str_ptr!(path) as *mut c_char, ft, &mut err)
+    };
+    if failed != 0 {
+        return Err(ParseET::CodegenError(llvm_message(err)).error().when(&format!("emitting {path}")))
+    }
+    Ok(())
+}
+
+/// like [emit_to_file], but returns the contents
+pub(crate) fn emit_to_memory(tm: LLVMTargetMachineRef, module: LLVMModuleRef, ft: LLVMCodeGenFileType) -> Result<Vec<u8>, ParseError> {
+    let mut err = ptr::null_mut();
+    let mut buffer = ptr::null_mut();
+    let failed = unsafe { target_machine::LLVMTargetMachineEmitToMemoryBuffer(tm, module, ft, &mut err, &mut buffer) };
+    if failed != 0 {
+        return Err(ParseET::CodegenError(llvm_message(err)).error())
+    }
+    Ok(buffer_bytes(buffer))
+}
+
+/// copies the contents of `buffer` and disposes it
+pub(crate) fn buffer_bytes(buffer: LLVMMemoryBufferRef) -> Vec<u8> {
+    unsafe {
+        let bytes = std::slice::from_raw_parts(core::LLVMGetBufferStart(buffer) as *const u8, core::LLVMGetBufferSize(buffer)).to_vec();
+        core::LLVMDisposeMemoryBuffer(buffer);
+        bytes
+    }
+}
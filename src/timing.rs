@@ -0,0 +1,92 @@
+use std::cell::RefCell;
+use std::time::{Duration, Instant};
+use llvm_sys::core;
+use llvm_sys::prelude::LLVMModuleRef;
+
+/// A phase of compilation with its accumulated wall time and counts of what it produced.
+/// Phases running more than once, like tokenizing several files, are summed up.
+struct Phase {
+    name: String,
+    time: Duration,
+    counts: Vec<(&'static str, usize)>,
+}
+
+thread_local! {
+    /// `None` unless [enable] was called, so timing costs nothing by default
+    static PHASES: RefCell<Option<Vec<Phase>>> = RefCell::new(None);
+}
+
+/// starts collecting timings and counts for [report]
+pub(crate) fn enable() {
+    PHASES.with(|phases| *phases.borrow_mut() = Some(vec![]))
+}
+
+fn record(name: &str, f: impl FnOnce(&mut Phase)) {
+    PHASES.with(|phases| if let Some(phases) = phases.borrow_mut().as_mut() {
+        let phase = match phases.iter().position(|p| p.name == name) {
+            Some(i) => &mut phases[i],
+            None => {
+                phases.push(Phase { name: name.to_string(), time: Duration::ZERO, counts: vec![] });
+                phases.last_mut().unwrap()
+            }
+        };
+        f(phase)
+    })
+}
+
+/// runs `f` as (part of) the phase `name`
+pub(crate) fn time<T>(name: &str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let r = f();
+    let elapsed = start.elapsed();
+    record(name, |phase| phase.time += elapsed);
+    r
+}
+
+/// adds `n` to the count of `what` in the phase `name`
+pub(crate) fn count(name: &str, what: &'static str, n: usize) {
+    record(name, |phase| match phase.counts.iter_mut().find(|(w, _)| *w == what) {
+        Some((_, count)) => *count += n,
+        None => phase.counts.push((what, n))
+    })
+}
+
+/// counts the defined functions, their basic blocks and instructions of `module` for the phase `name`
+pub(crate) fn count_module(name: &str, module: LLVMModuleRef) {
+    let (mut functions, mut blocks, mut instructions) = (0, 0, 0);
+    unsafe {
+        let mut function = core::LLVMGetFirstFunction(module);
+        while !function.is_null() {
+            if core::LLVMIsDeclaration(function) == 0 {
+                functions += 1;
+                let mut block = core::LLVMGetFirstBasicBlock(function);
+                while !block.is_null() {
+                    blocks += 1;
+                    let mut instruction = core::LLVMGetFirstInstruction(block);
+                    while !instruction.is_null() {
+                        instructions += 1;
+                        instruction = core::LLVMGetNextInstruction(instruction);
+                    }
+                    block = core::LLVMGetNextBasicBlock(block);
+                }
+            }
+            function = core::LLVMGetNextFunction(function);
+        }
+    }
+    count(name, "functions", functions);
+    count(name, "basic blocks", blocks);
+    count(name, "instructions", instructions);
+}
+
+/// prints the collected phases to stderr, if [enable] was called
+pub(crate) fn report() {
+    PHASES.with(|phases| if let Some(phases) = phases.borrow_mut().take() {
+        let total = phases.iter().map(|p| p.time).sum::<Duration>();
+        eprintln!("{:<20} {:>10}", "phase", "time");
+        for phase in phases.iter().chain([&Phase { name: "total".to_string(), time: total, counts: vec![] }]) {
+            let counts = phase.counts.iter().map(|(what, n)| format!("{n} {what}")).collect::<Vec<_>>().join(", ");
+            eprintln!("{:<20} {:>10} {:>5.1}%  {counts}", phase.name, format!("{:.2?}", phase.time),
+                      phase.time.as_secs_f64() * 100.0 / total.as_secs_f64().max(f64::MIN_POSITIVE));
+        }
+    })
+}
@@ -4,20 +4,20 @@ use crate::source::{ParseError, ParseET, Span};
 use crate::tokens::tokens::Token;
 
 #[derive(Debug, Clone)]
-pub(crate) struct TokIter {
+pub struct TokIter {
     vec: Rc<RefCell<Vec<Token>>>,
     pub(crate) index: usize,
 }
 
 impl TokIter {
-    pub(crate) fn new(vec: Vec<Token>) -> Self{
+    pub fn new(vec: Vec<Token>) -> Self{
         Self {
             vec: Rc::new(RefCell::new(vec)),
             index: 0,
         }
     }
 
-    pub(crate) fn get(&self, index: usize) -> Result<Token, ParseError> {
+    pub fn get(&self, index: usize) -> Result<Token, ParseError> {
         let v = self.vec.borrow();
         v.get(index).map(|t| t.clone())
             .ok_or_else(|| ParseET::EOF.at(self.nearest_point()
@@ -38,19 +38,19 @@ impl TokIter {
         }
     }
 
-    pub(crate) fn this(&self) -> Result<Token, ParseError>{
+    pub fn this(&self) -> Result<Token, ParseError>{
         self.get(self.index)
     }
 
-    pub(crate) fn next(&mut self){
+    pub fn next(&mut self){
         self.index += 1;
     }
 
-    pub(crate) fn len(&self) -> usize{
+    pub fn len(&self) -> usize{
         self.vec.borrow().len()
     }
 
-    pub(crate) fn left(&self) -> usize{
+    pub fn left(&self) -> usize{
         self.vec.borrow().len() - self.index
     }
 
@@ -4,7 +4,7 @@ use std::u128;
 use crate::source::{OnParseErr, ParseError, ParseET, Source, SourceIter, Span};
 use crate::tokens::tokens::{Literal, NumLit, NumLitTy, Token, TokenType};
 
-pub(crate) fn tokenize(source: Source) -> Result<Vec<Token>, ParseError>{
+pub fn tokenize(source: Source) -> Result<Vec<Token>, ParseError>{
     let mut iter = SourceIter::new(source);
     let mut tokens = vec![];
     while iter.left() > 0 {
@@ -2,9 +2,9 @@ use std::fmt::{Debug, Display, Formatter};
 use crate::source::Span;
 
 #[derive(Clone, PartialEq)]
-pub(crate) struct Token {
-    pub(crate) tt: TokenType,
-    pub(crate) loc: Span
+pub struct Token {
+    pub tt: TokenType,
+    pub loc: Span
 }
 
 impl Debug for Token {
@@ -16,10 +16,10 @@ impl Debug for Token {
 #[allow(non_camel_case_types)]
 /// `true` if the preceding character is also a particle with no spaces or any kind of separator.
 /// Keep in mind that a lot of characters qualify as a particle so it is not always safe to assume a value.
-pub(crate) type glued = bool;
+pub type glued = bool;
 
 #[derive(Debug, Clone, PartialEq)]
-pub(crate) enum TokenType {
+pub enum TokenType {
     Particle(char, glued),
     Ident(String),
     Literal(Literal)
@@ -32,7 +32,7 @@ impl TokenType {
 }
 
 #[derive(Clone, PartialEq)]
-pub(crate) enum Literal {
+pub enum Literal {
     String(String),
     Char(char),
     Number(NumLit, Option<NumLitTy>),
@@ -40,13 +40,13 @@ pub(crate) enum Literal {
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub(crate) enum NumLit {
+pub enum NumLit {
     Float(f64),
     Integer(u128)
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub(crate) enum NumLitTy {
+pub enum NumLitTy {
     U8,
     U16,
     U32,
@@ -0,0 +1,46 @@
+use std::{fs, panic, thread};
+use std::time::{Duration, SystemTime};
+use crate::cli::{Args, Subcommand};
+use crate::{driver, jit};
+use crate::source::ParseError;
+use crate::timing;
+
+/// how often the watched files are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Runs `command` and runs it again whenever one of the inputs or a file they `#include` changes, until killed.
+/// Errors and panics are printed instead of ending the watch, so a broken edit can be fixed in place.
+pub(crate) fn watch(args: &Args, command: impl Fn(&Args) -> Result<i32, ParseError> + panic::RefUnwindSafe) -> ! {
+    loop {
+        // the files are collected before compiling, so edits made while compiling are not missed
+        let files = driver::source_files(args);
+        let stamps = modified(&files);
+        if args.time_passes {
+            timing::enable()
+        }
+        let result = panic::catch_unwind(|| command(args));
+        jit::flush_c_stdout();
+        match result {
+            Ok(Ok(code)) if args.command == Subcommand::Run => eprintln!("[watch] exit code: {code}"),
+            Ok(Ok(_)) => eprintln!("[watch] build finished"),
+            Ok(Err(e)) => eprintln!("{e}"),
+            // the panic hook already printed the message
+            Err(_) => {}
+        }
+        timing::report();
+        eprintln!("[watch] waiting for changes to {} file(s)", files.len());
+        let changed = loop {
+            thread::sleep(POLL_INTERVAL);
+            let now = modified(&files);
+            if let Some(i) = (0..files.len()).find(|&i| now[i] != stamps[i]) {
+                break &files[i]
+            }
+        };
+        eprintln!("[watch] {changed} changed, recompiling");
+    }
+}
+
+/// modification times of `files`, `None` for files that are missing, e.g. while an editor replaces them
+fn modified(files: &[String]) -> Vec<Option<SystemTime>> {
+    files.iter().map(|file| fs::metadata(file).and_then(|m| m.modified()).ok()).collect()
+}
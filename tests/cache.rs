@@ -0,0 +1,46 @@
+use std::fs;
+use std::process::{Command, Output};
+
+fn build(dir: &std::path::Path, options: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_minimal_language"))
+        .args(["build", "main.mi", "--cache-dir", "cache"])
+        .args(options)
+        .current_dir(dir)
+        .output()
+        .expect("could not run minimal_language")
+}
+
+/// `#link`s are not part of the cached object, changing one still has to relink the executable
+#[test]
+fn changed_link_relinks() {
+    let dir = std::env::temp_dir().join(format!("minimal_language-cache-link-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("main.mi"), "#link m\n\nfn main do\nend\n").unwrap();
+    let first = build(&dir, &[]);
+    assert!(first.status.success(), "{}", String::from_utf8_lossy(&first.stderr));
+    fs::write(dir.join("main.mi"), "#link minimal_language_missing\n\nfn main do\nend\n").unwrap();
+    let second = build(&dir, &[]);
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(!second.status.success(), "the executable was not relinked with the missing library");
+}
+
+fn cached_objects(dir: &std::path::Path) -> usize {
+    fs::read_dir(dir.join("cache")).unwrap()
+        .filter(|entry| entry.as_ref().unwrap().path().extension().is_some_and(|ext| ext == "o"))
+        .count()
+}
+
+/// the same build reuses its object, a changed option compiles a new one
+#[test]
+fn changed_option_misses() {
+    let dir = std::env::temp_dir().join(format!("minimal_language-cache-option-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("main.mi"), "fn main do\nend\n").unwrap();
+    for options in [&[][..], &[], &["-O2"]] {
+        let output = build(&dir, options);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    }
+    let objects = cached_objects(&dir);
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(objects, 2);
+}
@@ -0,0 +1,15 @@
+use std::process::Command;
+
+/// runs `minimal_language test` on the testing directory, set `BLESS=1` to regenerate the `.expected` files
+#[test]
+fn testing_programs_match_expected_output() {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_minimal_language"));
+    command.args(["test", concat!(env!("CARGO_MANIFEST_DIR"), "/testing")]);
+    if std::env::var_os("BLESS").is_some() {
+        command.arg("--bless");
+    }
+    let output = command.output().expect("could not run minimal_language");
+    assert!(output.status.success(), "{}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr));
+}
@@ -0,0 +1,21 @@
+use std::fs;
+use std::process::Command;
+
+/// files including each other are reported with the whole chain of includes
+#[test]
+fn include_cycle_prints_chain() {
+    let dir = std::env::temp_dir().join(format!("minimal_language-cycle-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("a.mi"), "#include b\n\nfn main do\nend\n").unwrap();
+    fs::write(dir.join("b.mi"), "\n#include a\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_minimal_language"))
+        .args(["check", "a.mi"])
+        .current_dir(&dir)
+        .output()
+        .expect("could not run minimal_language");
+    fs::remove_dir_all(&dir).unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("include cycle: a.mi -> b.mi -> a.mi"), "{stderr}");
+    assert!(stderr.contains("at: b.mi: 2:0") && stderr.contains("included from a.mi:1"), "{stderr}");
+}
@@ -0,0 +1,22 @@
+use std::fs;
+use std::process::Command;
+
+/// errors in an included file point into that file and note where it was included
+#[test]
+fn include_error_points_into_included_file() {
+    let dir = std::env::temp_dir().join(format!("minimal_language-include-error-{}", std::process::id()));
+    fs::create_dir_all(dir.join("lib")).unwrap();
+    fs::write(dir.join("lib/x.mi"), "// helpers\nfn broken i32 do\n    return call + with literal i32 1 nope end\nend\n").unwrap();
+    fs::write(dir.join("main.mi"), "// main\n\n#include lib/x\n\nfn main do\nend\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_minimal_language"))
+        .args(["check", "main.mi"])
+        .current_dir(&dir)
+        .output()
+        .expect("could not run minimal_language");
+    fs::remove_dir_all(&dir).unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("at: lib/x.mi: 3:37"), "{stderr}");
+    assert!(stderr.contains("included from main.mi:3"), "{stderr}");
+    assert!(stderr.contains("  3 |     return call + with literal i32 1 nope end"), "{stderr}");
+}
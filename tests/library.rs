@@ -0,0 +1,25 @@
+use std::fs;
+use std::process::Command;
+
+/// a library of several files including the same api keeps it exported, even when optimized
+#[test]
+fn library_exports_included_fns() {
+    let dir = std::env::temp_dir().join(format!("minimal_language-library-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("api.mi"), "fn triple i32 with i32 x do\n    return call * with x literal i32 3 end\nend\n").unwrap();
+    fs::write(dir.join("mathlib.mi"), "#include api\n\nfn sixfold i32 with i32 x do\n    return call triple with call + with x x end end\nend\n").unwrap();
+    fs::write(dir.join("more.mi"), "#include api\n\nfn ninefold i32 with i32 x do\n    return call triple with call triple with x end end\nend\n").unwrap();
+    fs::write(dir.join("main.c"), "int triple(int); int sixfold(int); int ninefold(int);\n\
+        int main(void) { return triple(1) + sixfold(1) + ninefold(1); }\n").unwrap();
+    let build = Command::new(env!("CARGO_BIN_EXE_minimal_language"))
+        .args(["build", "mathlib.mi", "more.mi", "--crate-type=staticlib", "-O2", "--no-cache"])
+        .current_dir(&dir)
+        .output()
+        .expect("could not run minimal_language");
+    assert!(build.status.success(), "{}", String::from_utf8_lossy(&build.stderr));
+    let link = Command::new("cc").args(["main.c", "libmathlib.a", "-o", "main"]).current_dir(&dir).output().expect("could not run cc");
+    assert!(link.status.success(), "{}", String::from_utf8_lossy(&link.stderr));
+    let status = Command::new(dir.join("main")).status().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(status.code(), Some(18));
+}
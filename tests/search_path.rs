@@ -0,0 +1,44 @@
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn run(exe: &Path, dir: &Path, inc: &Path, mi_path: &Path) -> Output {
+    Command::new(exe)
+        .args(["run", "main.mi", "--no-cache", "-I"])
+        .arg(inc)
+        .env("MI_PATH", mi_path)
+        .current_dir(dir)
+        .output()
+        .expect("could not run minimal_language")
+}
+
+/// includes are searched next to the including file, then in `-I`, `MI_PATH` and the standard library,
+/// and every path tried is listed when none has the file
+#[test]
+fn include_search_order() {
+    let root = std::env::temp_dir().join(format!("minimal_language-search-path-{}", std::process::id()));
+    // a copy of the compiler in a prefix of its own, whose standard library is in `lib/minimal_language`
+    let exe = root.join("bin").join(Path::new(env!("CARGO_BIN_EXE_minimal_language")).file_name().unwrap());
+    let (src, inc, mi_path, stdlib) = (root.join("src"), root.join("inc"), root.join("mi_path"), root.join("lib/minimal_language"));
+    for dir in [&root.join("bin"), &src, &inc, &mi_path, &stdlib] {
+        fs::create_dir_all(dir).unwrap();
+    }
+    fs::copy(env!("CARGO_BIN_EXE_minimal_language"), &exe).unwrap();
+    fs::write(src.join("main.mi"), "#include which\n\nfn main i32 do\n    return call which end\nend\n").unwrap();
+    let dirs = [&src, &inc, &mi_path, &stdlib];
+    for (i, dir) in dirs.iter().enumerate() {
+        fs::write(dir.join("which.mi"), format!("fn which i32 do\n    return literal i32 {}\nend\n", i + 1)).unwrap();
+    }
+    let mut found = vec![];
+    for dir in dirs {
+        found.push(run(&exe, &src, &inc, &mi_path).status.code());
+        fs::remove_file(dir.join("which.mi")).unwrap();
+    }
+    let missing = run(&exe, &src, &inc, &mi_path);
+    fs::remove_dir_all(&root).unwrap();
+    assert_eq!(found, [Some(1), Some(2), Some(3), Some(4)]);
+    let stderr = String::from_utf8_lossy(&missing.stderr);
+    let tried = format!("tried which.mi, {}, {}, {}", inc.join("which.mi").display(), mi_path.join("which.mi").display(),
+                        stdlib.join("which.mi").display());
+    assert!(stderr.contains(&tried), "{stderr}");
+}